@@ -0,0 +1,199 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::*;
+
+/// Roles the owner can grant to accounts.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// can pause features
+    Pauser,
+    /// runs day to day bridge operations
+    Operator,
+    /// manages supported tokens
+    TokenManager,
+    /// can cancel timelocked proposals
+    Guardian,
+}
+
+/// Features that can be paused independently.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// native shield via deposit
+    Deposit,
+    /// fungible token shield via ft_on_transfer
+    FtDeposit,
+    /// withdraw and retry_withdrawal
+    Withdraw,
+    SubmitBurnProof,
+    ClaimCredit,
+    SwapBeaconCommittee,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 6] = [
+        Feature::Deposit,
+        Feature::FtDeposit,
+        Feature::Withdraw,
+        Feature::SubmitBurnProof,
+        Feature::ClaimCredit,
+        Feature::SwapBeaconCommittee,
+    ];
+
+    /// bit of the feature in `Vault::paused`
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+impl Vault {
+    pub(crate) fn assert_owner(&self) {
+        if env::predecessor_account_id() != self.owner_id {
+            BridgeError::NotOwner.panic();
+        }
+    }
+
+    /// owner implicitly holds every role
+    pub(crate) fn assert_role(&self, role: Role) {
+        let account_id = env::predecessor_account_id();
+        if account_id != self.owner_id && !self.roles.contains(&(role, account_id)) {
+            BridgeError::MissingRole.panic();
+        }
+    }
+
+    pub(crate) fn assert_not_paused(&self, feature: Feature) {
+        if self.paused & feature.bit() != 0 {
+            BridgeError::FeaturePaused.panic();
+        }
+    }
+
+    /// transfer ownership of the vault, executed through timelock
+    pub(crate) fn set_owner(&mut self, owner_id: AccountId) {
+        BridgeEvent::OwnerChanged {
+            old_owner_id: &self.owner_id,
+            new_owner_id: &owner_id,
+        }.emit();
+        self.owner_id = owner_id;
+    }
+
+    /// grant role to account, executed through timelock
+    pub(crate) fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        if self.roles.insert(&(role, account_id.clone())) {
+            BridgeEvent::RoleGranted { role, account_id: &account_id }.emit();
+        }
+    }
+
+    /// revoke role from account, executed through timelock
+    pub(crate) fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        if self.roles.remove(&(role, account_id.clone())) {
+            BridgeEvent::RoleRevoked { role, account_id: &account_id }.emit();
+        }
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// pause features, callable by pauser
+    pub fn pause(&mut self, features: Vec<Feature>) {
+        self.assert_role(Role::Pauser);
+        for feature in features.iter() {
+            self.paused |= feature.bit();
+        }
+        BridgeEvent::Paused { features: &features }.emit();
+    }
+
+    /// unpause features, callable by owner only
+    pub fn unpause(&mut self, features: Vec<Feature>) {
+        self.assert_owner();
+        for feature in features.iter() {
+            self.paused &= !feature.bit();
+        }
+        BridgeEvent::Unpaused { features: &features }.emit();
+    }
+
+    // getters
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// check account holds role, owner holds every role
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        account_id == self.owner_id || self.roles.contains(&(role, account_id))
+    }
+
+    /// list accounts granted the role
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        self.roles
+            .iter()
+            .filter(|(r, _)| *r == role)
+            .map(|(_, account_id)| account_id)
+            .collect()
+    }
+
+    pub fn is_paused(&self, feature: Feature) -> bool {
+        self.paused & feature.bit() != 0
+    }
+
+    /// list paused features
+    pub fn get_paused(&self) -> Vec<Feature> {
+        Feature::ALL.iter().copied().filter(|feature| self.is_paused(*feature)).collect()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{call_from, new_vault};
+
+    #[test]
+    fn test_pause() {
+        let mut vault = new_vault();
+        vault.grant_role(Role::Pauser, accounts(1));
+        assert_eq!(vault.get_role_members(Role::Pauser), vec![accounts(1)]);
+        assert!(vault.has_role(Role::Pauser, accounts(0)));
+        assert!(!vault.has_role(Role::Operator, accounts(1)));
+
+        call_from(accounts(1));
+        vault.pause(vec![Feature::Withdraw, Feature::Deposit]);
+        assert_eq!(vault.get_paused(), vec![Feature::Deposit, Feature::Withdraw]);
+
+        call_from(accounts(0));
+        vault.unpause(vec![Feature::Deposit]);
+        assert_eq!(vault.get_paused(), vec![Feature::Withdraw]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E36")]
+    fn test_pause_without_role() {
+        let mut vault = new_vault();
+        call_from(accounts(1));
+        vault.pause(vec![Feature::Withdraw]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E35")]
+    fn test_unpause_by_pauser() {
+        let mut vault = new_vault();
+        vault.grant_role(Role::Pauser, accounts(1));
+        call_from(accounts(1));
+        vault.pause(vec![Feature::Withdraw]);
+        vault.unpause(vec![Feature::Withdraw]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E37")]
+    fn test_deposit_paused() {
+        let mut vault = new_vault();
+        vault.pause(vec![Feature::Deposit]);
+        vault.deposit("incognito_address".to_string());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, near_bindgen, Gas, PromiseOrValue};
+
+use crate::admin::Feature;
+use crate::beacons::BeaconKey;
+use crate::borsh_request::BorshInteractRequest;
+use crate::errors::BridgeError;
+use crate::utils::{block_hash, check_proof};
+use crate::*;
+
+/// gas left required to start an item, covers proof verification and the costliest payout,
+/// registering the receiver from the storage pool before the transfer
+const GAS_FOR_WITHDRAWAL: Gas = Gas(100_000_000_000_000);
+
+/// Outcome of one proof in withdraw_batch.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WithdrawStatus {
+    /// transfer scheduled, failure is recorded as failed withdrawal
+    Sent,
+    /// queued by withdrawal delay or rate limit
+    Queued,
+    /// burn tx already used, skipped
+    AlreadyUsed,
+    /// not processed as the remaining gas can't cover it, submit again
+    Deferred,
+    /// proof rejected, see BridgeError codes
+    Rejected { code: u32, message: String },
+}
+
+impl From<BridgeError> for WithdrawStatus {
+    fn from(e: BridgeError) -> Self {
+        WithdrawStatus::Rejected { code: e.code(), message: e.to_string() }
+    }
+}
+
+/// Committees and block hashes shared by proofs of one batch.
+#[derive(Default)]
+struct ProofCache {
+    // committee by proof height
+    committees: HashMap<u128, Option<Vec<BeaconKey>>>,
+    // block hash by (blk_data, inst_root)
+    block_hashes: HashMap<([u8; 32], [u8; 32]), [u8; 32]>,
+}
+
+impl Vault {
+    /// verify proof and decode its withdraw instruction using the batch cache
+    fn check_withdraw_proof(&self, request: &InteractRequest, cache: &mut ProofCache) -> Result<Unshield, BridgeError> {
+        let beacons = cache.committees
+            .entry(request.height)
+            .or_insert_with(|| self.find_committee(request.height))
+            .as_ref()
+            .ok_or(BridgeError::BeaconNotFound)?;
+        let request = BorshInteractRequest::try_from(request)?;
+        let blk = *cache.block_hashes
+            .entry((request.blk_data, request.inst_root))
+            .or_insert_with(|| block_hash(&request));
+        check_proof(&request, beacons, &blk)?;
+
+        let unshield = Unshield::decode(&request.inst)?;
+        // checked upfront so a bad token fails the item instead of the batch
+        self.check_payable(&unshield.token, unshield.amount)?;
+
+        Ok(unshield)
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// withdraw multiple burn proofs, status returned per proof in order
+    ///
+    /// invalid proofs are rejected and used burn txs skipped without reverting the batch,
+    /// proofs the remaining gas can't cover are deferred
+    pub fn withdraw_batch(
+        &mut self,
+        unshield_infos: Vec<InteractRequest>,
+    ) -> Vec<WithdrawStatus> {
+        self.assert_not_paused(Feature::Withdraw);
+        let mut cache = ProofCache::default();
+
+        unshield_infos
+            .iter()
+            .map(|request| {
+                if env::prepaid_gas().0.saturating_sub(env::used_gas().0) < GAS_FOR_WITHDRAWAL.0 {
+                    return WithdrawStatus::Deferred;
+                }
+                let unshield = match self.check_withdraw_proof(request, &mut cache) {
+                    Ok(unshield) => unshield,
+                    Err(e) => return e.into(),
+                };
+                if self.tx_burn.get(&unshield.tx_id).unwrap_or_default() {
+                    return WithdrawStatus::AlreadyUsed;
+                }
+                match self.execute_unshield(unshield) {
+                    PromiseOrValue::Promise(_) => WithdrawStatus::Sent,
+                    PromiseOrValue::Value(_) => WithdrawStatus::Queued,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use crate::test_utils::{beacon, call_from, context};
+
+    fn request(height: u128) -> InteractRequest {
+        InteractRequest {
+            inst: "00".to_string(),
+            height,
+            inst_paths: vec![],
+            inst_path_is_lefts: vec![],
+            inst_root: [0u8; 32],
+            blk_data: [0u8; 32],
+            indexes: vec![0],
+            // not 64 bytes, rejected before ecrecover
+            signatures: vec!["00".to_string()],
+            vs: vec![0],
+        }
+    }
+
+    #[test]
+    fn test_bad_proofs_do_not_revert_batch() {
+        call_from(accounts(0));
+        let mut vault = Vault::new(vec![beacon()], U128Compat(10));
+
+        let statuses = vault.withdraw_batch(vec![request(5), request(10), request(10)]);
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(statuses[0], BridgeError::BeaconNotFound.into());
+        assert_eq!(statuses[1], BridgeError::InvalidBeaconSignature.into());
+        assert_eq!(statuses[2], statuses[1]);
+    }
+
+    #[test]
+    fn test_defer_without_gas() {
+        call_from(accounts(0));
+        let mut vault = Vault::new(vec![beacon()], U128Compat(10));
+        testing_env!(context(accounts(1)).prepaid_gas(Gas(GAS_FOR_WITHDRAWAL.0 - 1)).build());
+
+        let statuses = vault.withdraw_batch(vec![request(10), request(10)]);
+        assert_eq!(statuses, vec![WithdrawStatus::Deferred, WithdrawStatus::Deferred]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::convert::TryFrom;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen};
+
+use crate::errors::BridgeError;
+use crate::*;
+
+/// Uncompressed secp256k1 public key as returned by ecrecover, without the 0x04 prefix.
+pub type BeaconKey = [u8; 64];
+
+/// decode hex beacon key, None if not 64 bytes
+pub fn decode_beacon_key(key: &str) -> Option<BeaconKey> {
+    let key = hex::decode(key).ok()?;
+    <BeaconKey>::try_from(key.as_slice()).ok()
+}
+
+/// decode hex committee, a key that isn't 64 bytes never matched a recovered key
+/// so it is kept as zeros to preserve signer indexes
+pub(crate) fn decode_legacy_committee(beacons: &[String]) -> Vec<BeaconKey> {
+    beacons.iter()
+        .map(|key| decode_beacon_key(key).unwrap_or([0u8; 64]))
+        .collect()
+}
+
+impl Vault {
+    /// store committee from height and index its keys
+    pub(crate) fn insert_committee(&mut self, height: u128, beacons: &[BeaconKey]) {
+        for beacon in beacons.iter() {
+            self.beacon_index.insert(&env::keccak256_array(beacon), &height);
+        }
+        self.beacons.insert(&height, &beacons.to_vec());
+    }
+
+    /// committee signing at height, None if height precedes the first committee
+    ///
+    /// committees older than the one migrated from hex state are decoded from legacy_beacons
+    pub(crate) fn find_committee(&self, height: u128) -> Option<Vec<BeaconKey>> {
+        if let Some(key) = self.beacons.lower(&height.saturating_add(1)) {
+            return self.beacons.get(&key);
+        }
+        let key = self.legacy_beacons.lower(&height.saturating_add(1))?;
+        self.legacy_beacons.get(&key).as_deref().map(decode_legacy_committee)
+    }
+
+    pub(crate) fn committee(&self, height: u128) -> Vec<BeaconKey> {
+        self.find_committee(height)
+            .unwrap_or_else(|| BridgeError::BeaconNotFound.panic())
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    // getters
+
+    /// get height of the latest committee hex encoded beacon key was added in
+    pub fn get_beacon_height(&self, key: String) -> Option<U128> {
+        let key = decode_beacon_key(&key)?;
+        self.beacon_index.get(&env::keccak256_array(&key)).map(U128)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{beacon, call_from, new_vault};
+
+    #[test]
+    fn test_committee_bytes() {
+        let mut vault = new_vault();
+        vault.insert_committee(20, &[[2u8; 64], [1u8; 64]]);
+
+        assert_eq!(vault.committee(15), vec![[1u8; 64]]);
+        // views keep hex for compatibility
+        assert_eq!(vault.get_beacons(U128Compat(25)), vec![hex::encode([2u8; 64]), beacon()]);
+        assert_eq!(vault.get_beacon_height(hex::encode([1u8; 64])), Some(U128(20)));
+        assert_eq!(vault.get_beacon_height(hex::encode([3u8; 64])), None);
+    }
+
+    #[test]
+    fn test_legacy_committee_fallback() {
+        let mut vault = new_vault();
+        vault.beacons.clear();
+        vault.legacy_beacons.insert(&0, &vec![beacon(), "beacon".to_string()]);
+        vault.insert_committee(10, &[[2u8; 64]]);
+
+        assert_eq!(vault.committee(5), vec![[1u8; 64], [0u8; 64]]);
+        assert_eq!(vault.committee(10), vec![[2u8; 64]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E02")]
+    fn test_init_rejects_bad_key() {
+        call_from(accounts(0));
+        Vault::new(vec![hex::encode([1u8; 32])], U128Compat(10));
+    }
+}
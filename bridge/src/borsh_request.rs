@@ -0,0 +1,138 @@
+use std::convert::TryFrom;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{near_bindgen, PromiseOrValue};
+
+use crate::errors::BridgeError;
+use crate::instructions::decode_hex;
+use crate::*;
+
+/// Proof with raw bytes, borsh serialized by callers of the *_borsh entry points
+/// to skip hex decoding and JSON parsing of InteractRequest.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq)]
+pub struct BorshInteractRequest {
+    // instruction in bytes
+    pub inst: Vec<u8>,
+    // beacon height
+    pub height: u128,
+    // inst paths to build merkle tree
+    pub inst_paths: Vec<[u8; 32]>,
+    // bit i set if inst_paths[i] is a left node, least significant bit first
+    pub inst_path_is_lefts: Vec<u8>,
+    // instruction root
+    pub inst_root: [u8; 32],
+    // blkData
+    pub blk_data: [u8; 32],
+    // signature index
+    pub indexes: Vec<u8>,
+    // signatures, r || s
+    pub signatures: Vec<[u8; 64]>,
+    // v value
+    pub vs: Vec<u8>,
+}
+
+impl BorshInteractRequest {
+    /// check inst path at index is a left node
+    pub fn is_left(&self, index: usize) -> bool {
+        self.inst_path_is_lefts.get(index / 8)
+            .map(|bits| (bits >> (index % 8)) & 1 == 1)
+            .unwrap_or(false)
+    }
+
+    /// check bitmap has exactly one bit per inst path
+    pub fn has_valid_path_bitmap(&self) -> bool {
+        self.inst_path_is_lefts.len() == self.inst_paths.len().div_ceil(8)
+    }
+}
+
+/// pack inst path indicators into bitmap, least significant bit first
+pub fn pack_path_bits(lefts: &[bool]) -> Vec<u8> {
+    let mut bits = vec![0u8; lefts.len().div_ceil(8)];
+    for (i, _) in lefts.iter().enumerate().filter(|(_, left)| **left) {
+        bits[i / 8] |= 1 << (i % 8);
+    }
+
+    bits
+}
+
+impl TryFrom<&InteractRequest> for BorshInteractRequest {
+    type Error = BridgeError;
+
+    fn try_from(request: &InteractRequest) -> Result<Self, Self::Error> {
+        if request.inst_path_is_lefts.len() != request.inst_paths.len() {
+            return Err(BridgeError::InvalidMerkleTree);
+        }
+        let signatures = request.signatures.iter()
+            .map(|signature| {
+                let s_r = hex::decode(signature).map_err(|_| BridgeError::InvalidBeaconSignature)?;
+                <[u8; 64]>::try_from(s_r.as_slice()).map_err(|_| BridgeError::InvalidBeaconSignature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BorshInteractRequest {
+            inst: decode_hex(&request.inst)?,
+            height: request.height,
+            inst_paths: request.inst_paths.clone(),
+            inst_path_is_lefts: pack_path_bits(&request.inst_path_is_lefts),
+            inst_root: request.inst_root,
+            blk_data: request.blk_data,
+            indexes: request.indexes.clone(),
+            signatures,
+            vs: request.vs.clone(),
+        })
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// withdraw tokens with borsh serialized proof, see withdraw
+    pub fn withdraw_borsh(
+        &mut self,
+        #[serializer(borsh)] unshield_info: BorshInteractRequest,
+    ) -> PromiseOrValue<bool> {
+        self.withdraw_proof(unshield_info)
+    }
+
+    /// swap beacon committee with borsh serialized proof, see swap_beacon_committee
+    pub fn swap_beacon_committee_borsh(
+        &mut self,
+        #[serializer(borsh)] swap_info: BorshInteractRequest,
+    ) -> bool {
+        self.swap_beacon_committee_proof(swap_info)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json_request() {
+        let lefts = vec![true, false, false, true, false, false, false, false, true];
+        let request = InteractRequest {
+            inst: "9d01".to_string(),
+            height: 7,
+            inst_paths: vec![[1u8; 32]; lefts.len()],
+            inst_path_is_lefts: lefts.clone(),
+            inst_root: [2u8; 32],
+            blk_data: [3u8; 32],
+            indexes: vec![0],
+            signatures: vec![hex::encode([4u8; 64])],
+            vs: vec![1],
+        };
+        let proof = BorshInteractRequest::try_from(&request).unwrap();
+        assert_eq!(proof.inst, vec![0x9d, 0x01]);
+        assert_eq!(proof.inst_path_is_lefts, vec![0b0000_1001, 0b0000_0001]);
+        assert!(proof.has_valid_path_bitmap());
+        assert!((0..lefts.len()).all(|i| proof.is_left(i) == lefts[i]));
+        assert_eq!(proof.signatures, vec![[4u8; 64]]);
+
+        // borsh round trip as sent to withdraw_borsh
+        let bytes = proof.try_to_vec().unwrap();
+        assert_eq!(BorshInteractRequest::try_from_slice(&bytes).unwrap(), proof);
+
+        let short_signature = InteractRequest { signatures: vec!["00".to_string()], ..request.clone() };
+        assert_eq!(BorshInteractRequest::try_from(&short_signature), Err(BridgeError::InvalidBeaconSignature));
+        let missing_bit = InteractRequest { inst_path_is_lefts: vec![true], ..request };
+        assert_eq!(BorshInteractRequest::try_from(&missing_bit), Err(BridgeError::InvalidMerkleTree));
+    }
+}
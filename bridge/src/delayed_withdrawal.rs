@@ -0,0 +1,196 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen, Promise};
+
+use crate::admin::{Feature, Role};
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::*;
+
+/// Withdrawals of at least `threshold` wait `delay` before they can be finalized.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawalDelay {
+    // amount in incognito decimals (9)
+    pub threshold: U128,
+    // delay in nanoseconds
+    pub delay: U64,
+}
+
+impl Vault {
+    /// timestamp withdrawal of amount can be finalized from
+    pub(crate) fn withdrawal_unlock_at(&self, token: &str, amount: u128) -> u64 {
+        let now = env::block_timestamp();
+        match self.withdrawal_delays.get(&token.to_string()) {
+            Some(config) if amount >= config.threshold.0 => now.saturating_add(config.delay.0),
+            _ => now,
+        }
+    }
+
+    /// set or remove withdrawal delay of token, executed through timelock
+    pub(crate) fn set_withdrawal_delay(&mut self, token: String, config: Option<WithdrawalDelay>) {
+        match config {
+            Some(config) => self.withdrawal_delays.insert(&token, &config),
+            None => self.withdrawal_delays.remove(&token),
+        };
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// finalize pending withdrawal
+    ///
+    /// anyone can finalize once unlocked and the token rate limit has capacity,
+    /// relayer fee held back with the withdrawal is credited to the relayer
+    pub fn finalize_withdrawal(
+        &mut self,
+        tx_id: [u8; 32],
+    ) -> Promise {
+        self.assert_not_paused(Feature::Withdraw);
+        let pending = self.pending_withdrawals.remove(&tx_id)
+            .unwrap_or_else(|| BridgeError::PendingWithdrawalNotFound.panic());
+        if env::block_timestamp() < pending.unlock_at.0 {
+            BridgeError::WithdrawalLocked.panic();
+        }
+        self.assert_payable(&pending.token, pending.amount.0);
+        if !self.consume_queued_rate_limit(&pending.token, pending.amount.0) {
+            BridgeError::RateLimitExceeded.panic();
+        }
+        self.credit_relayer(&pending.token, pending.relayer_fee);
+        self.pay_withdrawal(tx_id, pending.receiver, pending.token, pending.amount.0, pending.msg)
+    }
+
+    /// veto pending withdrawal, callable by guardian
+    ///
+    /// burn tx stays used, vetoed amount and relayer fee remain in the vault
+    pub fn veto_withdrawal(
+        &mut self,
+        tx_id: [u8; 32],
+    ) {
+        self.assert_role(Role::Guardian);
+        let pending = self.pending_withdrawals.remove(&tx_id)
+            .unwrap_or_else(|| BridgeError::PendingWithdrawalNotFound.panic());
+        BridgeEvent::WithdrawalVetoed {
+            tx_id: hex::encode(tx_id),
+            receiver: &pending.receiver,
+            token: &pending.token,
+            amount: pending.amount,
+            by: &env::predecessor_account_id(),
+        }.emit();
+    }
+
+    // getters
+
+    pub fn get_withdrawal_delay(&self, token: String) -> Option<WithdrawalDelay> {
+        self.withdrawal_delays.get(&token)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::rate_limit::RateLimit;
+    use crate::relayer_fee::RelayerCredit;
+    use crate::test_utils::{call_at, new_vault};
+
+    const DELAY: u64 = 1_000;
+    const TX_ID: [u8; 32] = [5u8; 32];
+
+    fn setup_vault() -> Vault {
+        let mut vault = new_vault();
+        vault.set_withdrawal_delay(NEAR_ADDRESS.to_string(), Some(WithdrawalDelay { threshold: U128(100), delay: U64(DELAY) }));
+        let unlock_at = vault.withdrawal_unlock_at(NEAR_ADDRESS, 100);
+        vault.pending_withdrawals.insert(&TX_ID, &PendingWithdrawal {
+            receiver: accounts(1),
+            token: NEAR_ADDRESS.to_string(),
+            amount: U128(100),
+            queued_at: U64(0),
+            unlock_at: U64(unlock_at),
+            msg: None,
+            relayer_fee: Some(RelayerCredit { relayer: accounts(3), fee: U128(1) }),
+        });
+        vault
+    }
+
+    #[test]
+    fn test_unlock_at() {
+        let vault = setup_vault();
+        assert_eq!(vault.withdrawal_unlock_at(NEAR_ADDRESS, 99), 0);
+        assert_eq!(vault.withdrawal_unlock_at(NEAR_ADDRESS, 100), DELAY);
+    }
+
+    #[test]
+    fn test_finalize_after_delay() {
+        let mut vault = setup_vault();
+        call_at(accounts(2), DELAY);
+        vault.finalize_withdrawal(TX_ID);
+        assert!(vault.get_pending_withdrawal(TX_ID).is_none());
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(3)).0, 1);
+    }
+
+    #[test]
+    fn test_pending_withdrawal_round_trip() {
+        let vault = setup_vault();
+        let pending = vault.get_pending_withdrawal(TX_ID).unwrap();
+        assert!(pending.relayer_fee.is_some());
+        assert_eq!(PendingWithdrawal::try_from_slice(&pending.try_to_vec().unwrap()).unwrap(), pending);
+    }
+
+    #[test]
+    fn test_finalize_above_rate_limit_in_unused_window() {
+        let mut vault = setup_vault();
+        vault.set_rate_limit(NEAR_ADDRESS.to_string(), Some(RateLimit { max_amount: U128(50), window: U64(DELAY) }));
+        call_at(accounts(2), DELAY);
+        vault.finalize_withdrawal(TX_ID);
+        assert!(vault.get_pending_withdrawal(TX_ID).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E47")]
+    fn test_finalize_above_rate_limit_in_used_window() {
+        let mut vault = setup_vault();
+        vault.set_rate_limit(NEAR_ADDRESS.to_string(), Some(RateLimit { max_amount: U128(50), window: U64(DELAY) }));
+        call_at(accounts(2), DELAY);
+        assert!(vault.consume_rate_limit(NEAR_ADDRESS, 1));
+        vault.finalize_withdrawal(TX_ID);
+    }
+
+    #[test]
+    #[should_panic(expected = "E21")]
+    fn test_finalize_unknown_decimals() {
+        let mut vault = setup_vault();
+        let mut pending = vault.get_pending_withdrawal(TX_ID).unwrap();
+        pending.token = accounts(4).to_string();
+        vault.pending_withdrawals.insert(&TX_ID, &pending);
+        call_at(accounts(2), DELAY);
+        vault.finalize_withdrawal(TX_ID);
+    }
+
+    #[test]
+    #[should_panic(expected = "E49")]
+    fn test_finalize_before_delay() {
+        let mut vault = setup_vault();
+        call_at(accounts(2), DELAY - 1);
+        vault.finalize_withdrawal(TX_ID);
+    }
+
+    #[test]
+    fn test_veto() {
+        let mut vault = setup_vault();
+        vault.grant_role(Role::Guardian, accounts(2));
+        call_at(accounts(2), 1);
+        vault.veto_withdrawal(TX_ID);
+        assert!(vault.get_pending_withdrawal(TX_ID).is_none());
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(3)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E36")]
+    fn test_veto_without_role() {
+        let mut vault = setup_vault();
+        call_at(accounts(2), 1);
+        vault.veto_withdrawal(TX_ID);
+    }
+}
@@ -1,13 +1,202 @@
-pub const ERR28_WRONG_MSG_FORMAT: &str = "E28: Illegal msg in ft_transfer_call";
-pub const INVALID_MESSAGE: &str = "Message in FT transfer must not be empty";
-pub const _INVALID_INSTRUCTION: &str = "Invalid instruction length";
-pub const INVALID_METADATA: &str = "Invalid data in instruction";
-pub const INVALID_KEY_AND_INDEX: &str = "Invalid keys and indexes length in proof";
-pub const INVALID_BEACON_LIST: &str = "Beacons is empty";
-pub const INVALID_NUMBER_OF_SIGS: &str = "The total signature must reach majority of beacon list";
-pub const INVALID_BEACON_SIGNATURE: &str = "Invalid beacon signature";
-pub const INVALID_TX_BURN: &str = "Transaction burn already used";
-pub const INVALID_MERKLE_TREE: &str = "merkle tree root is not match";
-pub const VALUE_EXCEEDED: &str = "the total balance greater than max value allowed to shield";
-pub const PREV_COMMITTEE_HEIGHT_MISMATCH: &str = "Previous committee height mismatch";
-pub const COMMITTEE_HEIGHT_MISMATCH: &str = "Committee height mismatch";
\ No newline at end of file
+use std::fmt;
+use near_sdk::env;
+
+use crate::instructions::InstructionError;
+
+/// Bridge failures, displayed as `E<code>: <message>` so relayers can classify failed receipts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BridgeError {
+    InvalidKeyAndIndex,
+    InvalidBeaconList,
+    InvalidNumberOfSigs,
+    InvalidBeaconSignature,
+    InvalidTxBurn,
+    InvalidMerkleTree,
+    ValueExceeded,
+    PrevCommitteeHeightMismatch,
+    CommitteeHeightMismatch,
+    BeaconNotFound,
+    Instruction(InstructionError),
+    InvalidReceiverAccount,
+    InvalidTokenAccount,
+    TokenDecimalsNotFound,
+    AmountOverflow,
+    InvalidClaimAmount,
+    InsufficientCredit,
+    FailedWithdrawalNotFound,
+    NotWithdrawalReceiver,
+    WrongMsgFormat,
+    InvalidMessage,
+    PromiseFailed,
+    InvalidPromiseResult,
+    CallbackOnly,
+    AlreadyInitialized,
+    DuplicateSignerIndex,
+    SignerIndexOutOfRange,
+    NotOwner,
+    MissingRole,
+    FeaturePaused,
+    StateNotFound,
+    UnknownStateVersion,
+    UpgradeNotAuthorized,
+    CodeHashMismatch,
+    NoStagedCode,
+    ProposalNotFound,
+    ProposalNotReady,
+    InvalidProposal,
+    UpgradeAlreadyStaged,
+    RateLimitExceeded,
+    PendingWithdrawalNotFound,
+    WithdrawalLocked,
+    TokenNotRegistered,
+    TokenAlreadyRegistered,
+    InvalidTokenConfig,
+    DepositTooSmall,
+    InsufficientStorageDeposit,
+}
+
+impl BridgeError {
+    /// stable numeric code of the error
+    pub fn code(&self) -> u32 {
+        match self {
+            BridgeError::InvalidKeyAndIndex => 1,
+            BridgeError::InvalidBeaconList => 2,
+            BridgeError::InvalidNumberOfSigs => 3,
+            BridgeError::InvalidBeaconSignature => 4,
+            BridgeError::InvalidTxBurn => 5,
+            BridgeError::InvalidMerkleTree => 6,
+            BridgeError::ValueExceeded => 7,
+            BridgeError::PrevCommitteeHeightMismatch => 8,
+            BridgeError::CommitteeHeightMismatch => 9,
+            BridgeError::BeaconNotFound => 10,
+            BridgeError::Instruction(e) => match e {
+                InstructionError::InvalidHex => 11,
+                InstructionError::BadLength { .. } => 12,
+                InstructionError::BadTokenLen(_) => 13,
+                InstructionError::BadReceiverLen(_) => 14,
+                InstructionError::NonUtf8Token => 15,
+                InstructionError::NonUtf8Receiver => 16,
+                InstructionError::UnknownMetadata(_) => 17,
+                InstructionError::BadShard(_) => 18,
+                InstructionError::NonUtf8Msg => 54,
+            },
+            BridgeError::InvalidReceiverAccount => 19,
+            BridgeError::InvalidTokenAccount => 20,
+            BridgeError::TokenDecimalsNotFound => 21,
+            BridgeError::AmountOverflow => 22,
+            BridgeError::InvalidClaimAmount => 23,
+            BridgeError::InsufficientCredit => 24,
+            BridgeError::FailedWithdrawalNotFound => 25,
+            BridgeError::NotWithdrawalReceiver => 26,
+            BridgeError::InvalidMessage => 27,
+            BridgeError::WrongMsgFormat => 28,
+            BridgeError::PromiseFailed => 29,
+            BridgeError::InvalidPromiseResult => 30,
+            BridgeError::CallbackOnly => 31,
+            BridgeError::AlreadyInitialized => 32,
+            BridgeError::DuplicateSignerIndex => 33,
+            BridgeError::SignerIndexOutOfRange => 34,
+            BridgeError::NotOwner => 35,
+            BridgeError::MissingRole => 36,
+            BridgeError::FeaturePaused => 37,
+            BridgeError::StateNotFound => 38,
+            BridgeError::UnknownStateVersion => 39,
+            BridgeError::UpgradeNotAuthorized => 40,
+            BridgeError::CodeHashMismatch => 41,
+            BridgeError::NoStagedCode => 42,
+            BridgeError::ProposalNotFound => 43,
+            BridgeError::ProposalNotReady => 44,
+            BridgeError::InvalidProposal => 45,
+            BridgeError::UpgradeAlreadyStaged => 46,
+            BridgeError::RateLimitExceeded => 47,
+            BridgeError::PendingWithdrawalNotFound => 48,
+            BridgeError::WithdrawalLocked => 49,
+            BridgeError::TokenNotRegistered => 50,
+            BridgeError::TokenAlreadyRegistered => 51,
+            BridgeError::InvalidTokenConfig => 52,
+            BridgeError::DepositTooSmall => 53,
+            BridgeError::InsufficientStorageDeposit => 55,
+        }
+    }
+
+    /// abort execution with this error
+    pub fn panic(&self) -> ! {
+        env::panic_str(&self.to_string())
+    }
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:02}: ", self.code())?;
+        match self {
+            BridgeError::InvalidKeyAndIndex => write!(f, "Invalid keys and indexes length in proof"),
+            BridgeError::InvalidBeaconList => write!(f, "Beacons is empty or has a key other than 64 bytes hex"),
+            BridgeError::InvalidNumberOfSigs => write!(f, "The total signature must reach majority of beacon list"),
+            BridgeError::InvalidBeaconSignature => write!(f, "Invalid beacon signature"),
+            BridgeError::InvalidTxBurn => write!(f, "Transaction burn already used"),
+            BridgeError::InvalidMerkleTree => write!(f, "merkle tree root is not match"),
+            BridgeError::ValueExceeded => write!(f, "the total balance greater than max value allowed to shield"),
+            BridgeError::PrevCommitteeHeightMismatch => write!(f, "Previous committee height mismatch"),
+            BridgeError::CommitteeHeightMismatch => write!(f, "Committee height mismatch"),
+            BridgeError::BeaconNotFound => write!(f, "No beacon committee at height"),
+            BridgeError::Instruction(e) => write!(f, "{}", e),
+            BridgeError::InvalidReceiverAccount => write!(f, "Invalid receiver account id"),
+            BridgeError::InvalidTokenAccount => write!(f, "Invalid token account id"),
+            BridgeError::TokenDecimalsNotFound => write!(f, "Token decimals not found"),
+            BridgeError::AmountOverflow => write!(f, "Amount overflow"),
+            BridgeError::InvalidClaimAmount => write!(f, "Claim amount must be greater than zero"),
+            BridgeError::InsufficientCredit => write!(f, "Claim amount exceeds credit balance"),
+            BridgeError::FailedWithdrawalNotFound => write!(f, "Failed withdrawal not found"),
+            BridgeError::NotWithdrawalReceiver => write!(f, "Only withdrawal receiver can claim it"),
+            BridgeError::InvalidMessage => write!(f, "Message in FT transfer must not be empty"),
+            BridgeError::WrongMsgFormat => write!(f, "Illegal msg in ft_transfer_call"),
+            BridgeError::PromiseFailed => write!(f, "Cross contract call failed"),
+            BridgeError::InvalidPromiseResult => write!(f, "Invalid cross contract call result"),
+            BridgeError::CallbackOnly => write!(f, "This is a callback method"),
+            BridgeError::AlreadyInitialized => write!(f, "Already initialized"),
+            BridgeError::DuplicateSignerIndex => write!(f, "Duplicate signer index in proof"),
+            BridgeError::SignerIndexOutOfRange => write!(f, "Signer index out of beacon list range"),
+            BridgeError::NotOwner => write!(f, "Only owner can call this method"),
+            BridgeError::MissingRole => write!(f, "Caller is missing required role"),
+            BridgeError::FeaturePaused => write!(f, "Feature is paused"),
+            BridgeError::StateNotFound => write!(f, "Contract state not found"),
+            BridgeError::UnknownStateVersion => write!(f, "Unknown contract state version"),
+            BridgeError::UpgradeNotAuthorized => write!(f, "No upgrade authorized by beacon committee"),
+            BridgeError::CodeHashMismatch => write!(f, "Code hash does not match authorized upgrade"),
+            BridgeError::NoStagedCode => write!(f, "Upgrade code is not staged"),
+            BridgeError::ProposalNotFound => write!(f, "Proposal not found"),
+            BridgeError::ProposalNotReady => write!(f, "Proposal timelock has not passed"),
+            BridgeError::InvalidProposal => write!(f, "Action can't be proposed directly"),
+            BridgeError::UpgradeAlreadyStaged => write!(f, "Upgrade code is already staged"),
+            BridgeError::RateLimitExceeded => write!(f, "Amount exceeds token rate limit capacity"),
+            BridgeError::PendingWithdrawalNotFound => write!(f, "Pending withdrawal not found"),
+            BridgeError::WithdrawalLocked => write!(f, "Withdrawal delay has not passed"),
+            BridgeError::TokenNotRegistered => write!(f, "Token not registered"),
+            BridgeError::TokenAlreadyRegistered => write!(f, "Token already registered"),
+            BridgeError::InvalidTokenConfig => write!(f, "Invalid token config"),
+            BridgeError::DepositTooSmall => write!(f, "Deposit below receipt storage cost plus one incognito unit"),
+            BridgeError::InsufficientStorageDeposit => write!(f, "Attached deposit does not cover storage"),
+        }
+    }
+}
+
+impl From<InstructionError> for BridgeError {
+    fn from(e: InstructionError) -> Self {
+        BridgeError::Instruction(e)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(BridgeError::WrongMsgFormat.to_string(), "E28: Illegal msg in ft_transfer_call");
+        assert_eq!(BridgeError::InvalidKeyAndIndex.to_string(), "E01: Invalid keys and indexes length in proof");
+        assert_eq!(
+            BridgeError::from(InstructionError::UnknownMetadata(1)).to_string(),
+            "E17: Invalid data in instruction: unknown metadata 1"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use near_sdk::serde::Serialize;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, serde_json, AccountId};
+
+use crate::admin::{Feature, Role};
+use crate::timelock::Proposal;
+use crate::token_receiver::RefundReason;
+use crate::token_registry::TokenConfig;
+
+pub const EVENT_STANDARD: &str = "incognito-bridge";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+/// Bridge events, logged in NEP-297 format:
+/// `EVENT_JSON:{"standard":"incognito-bridge","version":"1.0.0","event":...,"data":...}`
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeEvent<'a> {
+    /// native or fungible token shielded, amount in incognito decimals (9)
+    Shield {
+        token: &'a str,
+        amount: U128,
+        incognito_address: &'a str,
+        nonce: U64,
+    },
+    /// fungible token deposit returned to sender, amount in token decimals
+    DepositRefunded {
+        token: &'a AccountId,
+        amount: U128,
+        reason: RefundReason,
+    },
+    /// ledger of token differs from the vault balance on the token contract
+    BalanceDiscrepancy {
+        token: &'a AccountId,
+        locked: U128,
+        balance: U128,
+    },
+    /// burn proof accepted by withdraw, amount in incognito decimals (9)
+    /// relayer_fee is carved from amount and credited to relayer, receiver gets the rest
+    Unshield {
+        tx_id: String,
+        receiver: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+        relayer: &'a AccountId,
+        relayer_fee: U128,
+    },
+    /// transfer of unshield failed, withdrawal kept for retry or claim
+    WithdrawFailed {
+        tx_id: String,
+        receiver: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+    },
+    /// large withdrawal or withdrawal beyond token rate limit, queued until finalized
+    WithdrawalQueued {
+        tx_id: String,
+        receiver: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+        unlock_at: U64,
+    },
+    /// pending withdrawal vetoed by guardian
+    WithdrawalVetoed {
+        tx_id: String,
+        receiver: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+        by: &'a AccountId,
+    },
+    /// amount refunded by receiver of ft_transfer_call, credited to receiver
+    WithdrawRefundCredited {
+        tx_id: String,
+        receiver: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+    },
+    /// withdrawal receiver registered on token from the storage pool, fee in incognito decimals (9)
+    StorageDepositPaid {
+        receiver: &'a AccountId,
+        token: &'a str,
+        deposit: U128,
+        fee: U128,
+    },
+    StoragePoolToppedUp {
+        account: &'a AccountId,
+        amount: U128,
+        pool: U128,
+    },
+    /// burn proof accepted by submit_burn_proof and credited to account
+    BurnProofCredit {
+        tx_id: String,
+        account: &'a str,
+        token: &'a str,
+        amount: U128,
+    },
+    /// credit spent by claim_credit
+    ClaimCredit {
+        account: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+    },
+    /// credit keyed by hex encoded ids before state version 2 moved to plain ids
+    HexCreditRecovered {
+        account: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+    },
+    /// transfer of claim failed, credit restored
+    ClaimCreditFailed {
+        account: &'a AccountId,
+        token: &'a str,
+        amount: U128,
+    },
+    /// beacon committee swapped
+    SwapBeaconCommittee {
+        prev_height: U128,
+        height: U128,
+        num_vals: U128,
+    },
+    /// vault ownership transferred
+    OwnerChanged {
+        old_owner_id: &'a AccountId,
+        new_owner_id: &'a AccountId,
+    },
+    RoleGranted {
+        role: Role,
+        account_id: &'a AccountId,
+    },
+    RoleRevoked {
+        role: Role,
+        account_id: &'a AccountId,
+    },
+    Paused {
+        features: &'a [Feature],
+    },
+    Unpaused {
+        features: &'a [Feature],
+    },
+    /// beacon committee authorized code hash for upgrade
+    UpgradeAuthorized {
+        code_hash: String,
+    },
+    UpgradeStaged {
+        code_hash: String,
+    },
+    UpgradeDeployed {
+        code_hash: String,
+    },
+    /// token registered or its config updated through timelock
+    TokenRegistered {
+        token: &'a AccountId,
+        config: &'a TokenConfig,
+    },
+    TokenDeregistered {
+        token: &'a AccountId,
+    },
+    /// admin action queued behind timelock
+    ProposalQueued {
+        proposal: &'a Proposal,
+    },
+    ProposalExecuted {
+        id: U64,
+    },
+    /// proposal cancelled by guardian
+    ProposalCancelled {
+        id: U64,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a BridgeEvent<'a>,
+}
+
+impl BridgeEvent<'_> {
+    pub fn to_json_string(&self) -> String {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        format!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap())
+    }
+
+    pub fn emit(&self) {
+        env::log_str(&self.to_json_string());
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shield_event() {
+        let event = BridgeEvent::Shield {
+            token: "token.near",
+            amount: U128(100),
+            incognito_address: "my_address",
+            nonce: U64(1),
+        };
+        assert_eq!(
+            event.to_json_string(),
+            r#"EVENT_JSON:{"standard":"incognito-bridge","version":"1.0.0","event":"shield","data":{"token":"token.near","amount":"100","incognito_address":"my_address","nonce":"1"}}"#
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::convert::TryFrom;
+use std::fmt;
+use arrayref::{array_refs, array_ref};
+
+use crate::utils::{WITHDRAW_INST_LEN, SWAP_COMMITTEE_INST_LEN, UPGRADE_INST_LEN, WITHDRAW_METADATA, SWAP_BEACON_METADATA, BURN_METADATA, UPGRADE_METADATA, WITHDRAW_CALL_METADATA};
+
+const MAX_ADDRESS_LEN: usize = 64;
+// swap instructions carry uncompressed 64 byte keys since state version 2, the beacon
+// chain has to emit them before this code is deployed, see README
+const BEACON_KEY_LEN: usize = 64;
+const SHARD_ID: u8 = 1;
+
+/// Reasons an instruction can't be decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionError {
+    InvalidHex,
+    BadLength { expected: usize, actual: usize },
+    BadTokenLen(u8),
+    BadReceiverLen(u8),
+    NonUtf8Token,
+    NonUtf8Receiver,
+    UnknownMetadata(u8),
+    BadShard(u8),
+    NonUtf8Msg,
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::InvalidHex => write!(f, "Instruction is not valid hex"),
+            InstructionError::BadLength { expected, actual } =>
+                write!(f, "Invalid instruction length: expected {}, got {}", expected, actual),
+            InstructionError::BadTokenLen(len) => write!(f, "Invalid token length in instruction: {}", len),
+            InstructionError::BadReceiverLen(len) => write!(f, "Invalid receiver length in instruction: {}", len),
+            InstructionError::NonUtf8Token => write!(f, "Token in instruction is not utf8"),
+            InstructionError::NonUtf8Receiver => write!(f, "Receiver in instruction is not utf8"),
+            InstructionError::UnknownMetadata(meta) => write!(f, "Invalid data in instruction: unknown metadata {}", meta),
+            InstructionError::BadShard(shard) => write!(f, "Invalid data in instruction: unknown shard {}", shard),
+            InstructionError::NonUtf8Msg => write!(f, "Message in instruction is not utf8"),
+        }
+    }
+}
+
+/// Decode hex instruction as submitted in `InteractRequest`.
+pub fn decode_hex(inst: &str) -> Result<Vec<u8>, InstructionError> {
+    hex::decode(inst).map_err(|_| InstructionError::InvalidHex)
+}
+
+/// Unshield instruction, paid out directly by withdraw.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawInst {
+    pub token: String,
+    pub receiver: String,
+    // amount in incognito decimals (9)
+    pub amount: u64,
+    pub tx_id: [u8; 32],
+}
+
+/// Unshield instruction paid out by withdraw through `ft_transfer_call` with `msg`.
+///
+/// Layout is the withdraw layout followed by 2 bytes message length and the utf8 message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawCallInst {
+    pub token: String,
+    pub receiver: String,
+    // amount in incognito decimals (9)
+    pub amount: u64,
+    pub tx_id: [u8; 32],
+    pub msg: String,
+}
+
+/// Burn instruction, credited to receiver by submit_burn_proof.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnInst {
+    pub token: String,
+    pub receiver: String,
+    // amount in incognito decimals (9)
+    pub amount: u64,
+    pub tx_id: [u8; 32],
+}
+
+/// Beacon committee swap instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapBeaconInst {
+    pub prev_height: u128,
+    pub height: u128,
+    // 64 bytes uncompressed beacon keys, as recovered from signatures
+    pub beacons: Vec<[u8; 64]>,
+}
+
+/// Contract upgrade instruction, authorizes deploying code with given sha256 hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradeInst {
+    pub code_hash: [u8; 32],
+    pub tx_id: [u8; 32],
+}
+
+/// Any instruction the bridge understands, dispatched by metadata type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Withdraw(WithdrawInst),
+    WithdrawCall(WithdrawCallInst),
+    Burn(BurnInst),
+    SwapBeacon(SwapBeaconInst),
+    Upgrade(UpgradeInst),
+}
+
+fn check_header(inst: &[u8], meta_type: u8, min_len: usize) -> Result<(), InstructionError> {
+    if inst.len() < min_len {
+        return Err(InstructionError::BadLength { expected: min_len, actual: inst.len() });
+    }
+    if inst[0] != meta_type {
+        return Err(InstructionError::UnknownMetadata(inst[0]));
+    }
+    if inst[1] != SHARD_ID {
+        return Err(InstructionError::BadShard(inst[1]));
+    }
+    Ok(())
+}
+
+/// token, receiver, amount and tx id shared by withdraw and burn layout
+fn decode_transfer(inst: &[u8], meta_type: u8) -> Result<(String, String, u64, [u8; 32]), InstructionError> {
+    check_header(inst, meta_type, WITHDRAW_INST_LEN)?;
+    let inst_ = array_ref![inst, 0, WITHDRAW_INST_LEN];
+    #[allow(clippy::ptr_offset_with_cast)]
+    let (_, _, token_len, token, receiver_len, receiver, _, amount, tx_id) =
+        array_refs![inst_, 1, 1, 1, 64, 1, 64, 24, 8, 32];
+    let token_len = u8::from_be_bytes(*token_len);
+    let receiver_len = u8::from_be_bytes(*receiver_len);
+    if token_len as usize > MAX_ADDRESS_LEN {
+        return Err(InstructionError::BadTokenLen(token_len));
+    }
+    if receiver_len as usize > MAX_ADDRESS_LEN {
+        return Err(InstructionError::BadReceiverLen(receiver_len));
+    }
+    let token = String::from_utf8(token[MAX_ADDRESS_LEN - token_len as usize..].to_vec())
+        .map_err(|_| InstructionError::NonUtf8Token)?;
+    let receiver = String::from_utf8(receiver[MAX_ADDRESS_LEN - receiver_len as usize..].to_vec())
+        .map_err(|_| InstructionError::NonUtf8Receiver)?;
+
+    Ok((token, receiver, u64::from_be_bytes(*amount), *tx_id))
+}
+
+fn encode_transfer(meta_type: u8, token: &str, receiver: &str, amount: u64, tx_id: &[u8; 32]) -> Vec<u8> {
+    let mut inst = vec![meta_type, SHARD_ID, token.len() as u8];
+    inst.extend_from_slice(&[0u8; MAX_ADDRESS_LEN][token.len()..]);
+    inst.extend_from_slice(token.as_bytes());
+    inst.push(receiver.len() as u8);
+    inst.extend_from_slice(&[0u8; MAX_ADDRESS_LEN][receiver.len()..]);
+    inst.extend_from_slice(receiver.as_bytes());
+    inst.extend_from_slice(&[0u8; 24]);
+    inst.extend_from_slice(&amount.to_be_bytes());
+    inst.extend_from_slice(tx_id);
+    inst
+}
+
+impl TryFrom<&[u8]> for WithdrawInst {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        let (token, receiver, amount, tx_id) = decode_transfer(inst, WITHDRAW_METADATA)?;
+        Ok(WithdrawInst { token, receiver, amount, tx_id })
+    }
+}
+
+impl WithdrawInst {
+    pub fn encode(&self) -> Vec<u8> {
+        encode_transfer(WITHDRAW_METADATA, &self.token, &self.receiver, self.amount, &self.tx_id)
+    }
+}
+
+impl TryFrom<&[u8]> for WithdrawCallInst {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        let (token, receiver, amount, tx_id) = decode_transfer(inst, WITHDRAW_CALL_METADATA)?;
+        let header_len = WITHDRAW_INST_LEN + 2;
+        if inst.len() < header_len {
+            return Err(InstructionError::BadLength { expected: header_len, actual: inst.len() });
+        }
+        let msg_len = u16::from_be_bytes(*array_ref![inst, WITHDRAW_INST_LEN, 2]) as usize;
+        let expected = header_len + msg_len;
+        if inst.len() < expected {
+            return Err(InstructionError::BadLength { expected, actual: inst.len() });
+        }
+        let msg = String::from_utf8(inst[header_len..expected].to_vec())
+            .map_err(|_| InstructionError::NonUtf8Msg)?;
+
+        Ok(WithdrawCallInst { token, receiver, amount, tx_id, msg })
+    }
+}
+
+impl WithdrawCallInst {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inst = encode_transfer(WITHDRAW_CALL_METADATA, &self.token, &self.receiver, self.amount, &self.tx_id);
+        inst.extend_from_slice(&(self.msg.len() as u16).to_be_bytes());
+        inst.extend_from_slice(self.msg.as_bytes());
+        inst
+    }
+}
+
+impl TryFrom<&[u8]> for BurnInst {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        let (token, receiver, amount, tx_id) = decode_transfer(inst, BURN_METADATA)?;
+        Ok(BurnInst { token, receiver, amount, tx_id })
+    }
+}
+
+impl BurnInst {
+    pub fn encode(&self) -> Vec<u8> {
+        encode_transfer(BURN_METADATA, &self.token, &self.receiver, self.amount, &self.tx_id)
+    }
+}
+
+impl TryFrom<&[u8]> for SwapBeaconInst {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        check_header(inst, SWAP_BEACON_METADATA, SWAP_COMMITTEE_INST_LEN)?;
+        let inst_ = array_ref![inst, 0, SWAP_COMMITTEE_INST_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (_, _, _, prev_height, _, height, _, num_vals) =
+            array_refs![inst_, 1, 1, 16, 16, 16, 16, 16, 16];
+        let num_vals = usize::try_from(u128::from_be_bytes(*num_vals)).unwrap_or(usize::MAX);
+
+        let expected = num_vals.saturating_mul(BEACON_KEY_LEN).saturating_add(SWAP_COMMITTEE_INST_LEN);
+        if inst.len() < expected {
+            return Err(InstructionError::BadLength { expected, actual: inst.len() });
+        }
+        let beacons = inst[SWAP_COMMITTEE_INST_LEN..expected]
+            .chunks(BEACON_KEY_LEN)
+            .map(|key| *array_ref![key, 0, BEACON_KEY_LEN])
+            .collect();
+
+        Ok(SwapBeaconInst {
+            prev_height: u128::from_be_bytes(*prev_height),
+            height: u128::from_be_bytes(*height),
+            beacons,
+        })
+    }
+}
+
+impl SwapBeaconInst {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inst = vec![SWAP_BEACON_METADATA, SHARD_ID];
+        for value in [self.prev_height, self.height, self.beacons.len() as u128].iter() {
+            inst.extend_from_slice(&[0u8; 16]);
+            inst.extend_from_slice(&value.to_be_bytes());
+        }
+        for beacon in self.beacons.iter() {
+            inst.extend_from_slice(beacon);
+        }
+        inst
+    }
+}
+
+impl TryFrom<&[u8]> for UpgradeInst {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        check_header(inst, UPGRADE_METADATA, UPGRADE_INST_LEN)?;
+        let inst_ = array_ref![inst, 0, UPGRADE_INST_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (_, _, code_hash, tx_id) = array_refs![inst_, 1, 1, 32, 32];
+
+        Ok(UpgradeInst { code_hash: *code_hash, tx_id: *tx_id })
+    }
+}
+
+impl UpgradeInst {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inst = vec![UPGRADE_METADATA, SHARD_ID];
+        inst.extend_from_slice(&self.code_hash);
+        inst.extend_from_slice(&self.tx_id);
+        inst
+    }
+}
+
+impl TryFrom<&[u8]> for Instruction {
+    type Error = InstructionError;
+
+    fn try_from(inst: &[u8]) -> Result<Self, Self::Error> {
+        match inst.first() {
+            Some(&WITHDRAW_METADATA) => WithdrawInst::try_from(inst).map(Instruction::Withdraw),
+            Some(&WITHDRAW_CALL_METADATA) => WithdrawCallInst::try_from(inst).map(Instruction::WithdrawCall),
+            Some(&BURN_METADATA) => BurnInst::try_from(inst).map(Instruction::Burn),
+            Some(&SWAP_BEACON_METADATA) => SwapBeaconInst::try_from(inst).map(Instruction::SwapBeacon),
+            Some(&UPGRADE_METADATA) => UpgradeInst::try_from(inst).map(Instruction::Upgrade),
+            Some(meta_type) => Err(InstructionError::UnknownMetadata(*meta_type)),
+            None => Err(InstructionError::BadLength { expected: 1, actual: 0 }),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn withdraw_inst() -> WithdrawInst {
+        WithdrawInst {
+            token: "token.near".to_string(),
+            receiver: "receiver.near".to_string(),
+            amount: 1_000_000_000,
+            tx_id: [9u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_withdraw_round_trip() {
+        let inst = withdraw_inst();
+        let encoded = inst.encode();
+        assert_eq!(encoded.len(), WITHDRAW_INST_LEN);
+        assert_eq!(WithdrawInst::try_from(encoded.as_slice()), Ok(inst.clone()));
+        assert_eq!(Instruction::try_from(encoded.as_slice()), Ok(Instruction::Withdraw(inst)));
+    }
+
+    #[test]
+    fn test_withdraw_call_round_trip() {
+        let inst = WithdrawCallInst {
+            token: "token.near".to_string(),
+            receiver: "dex.near".to_string(),
+            amount: 5,
+            tx_id: [2u8; 32],
+            msg: r#"{"actions":[]}"#.to_string(),
+        };
+        let encoded = inst.encode();
+        assert_eq!(encoded.len(), WITHDRAW_INST_LEN + 2 + inst.msg.len());
+        assert_eq!(Instruction::try_from(encoded.as_slice()), Ok(Instruction::WithdrawCall(inst)));
+        assert_eq!(
+            WithdrawCallInst::try_from(&encoded[..encoded.len() - 1]),
+            Err(InstructionError::BadLength { expected: encoded.len(), actual: encoded.len() - 1 })
+        );
+    }
+
+    #[test]
+    fn test_burn_round_trip() {
+        let inst = BurnInst {
+            token: "token.near".to_string(),
+            receiver: "receiver.near".to_string(),
+            amount: 7,
+            tx_id: [1u8; 32],
+        };
+        assert_eq!(BurnInst::try_from(inst.encode().as_slice()), Ok(inst));
+    }
+
+    #[test]
+    fn test_swap_beacon_round_trip() {
+        let inst = SwapBeaconInst {
+            prev_height: 10,
+            height: 20,
+            beacons: vec![[1u8; 64], [2u8; 64]],
+        };
+        let encoded = inst.encode();
+        assert_eq!(encoded.len(), SWAP_COMMITTEE_INST_LEN + 128);
+        assert_eq!(SwapBeaconInst::try_from(encoded.as_slice()), Ok(inst));
+    }
+
+    #[test]
+    fn test_upgrade_round_trip() {
+        let inst = UpgradeInst { code_hash: [3u8; 32], tx_id: [4u8; 32] };
+        let encoded = inst.encode();
+        assert_eq!(encoded.len(), UPGRADE_INST_LEN);
+        assert_eq!(Instruction::try_from(encoded.as_slice()), Ok(Instruction::Upgrade(inst)));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let encoded = withdraw_inst().encode();
+        assert_eq!(
+            WithdrawInst::try_from(&encoded[..100]),
+            Err(InstructionError::BadLength { expected: WITHDRAW_INST_LEN, actual: 100 })
+        );
+        assert_eq!(BurnInst::try_from(encoded.as_slice()), Err(InstructionError::UnknownMetadata(WITHDRAW_METADATA)));
+        assert_eq!(Instruction::try_from(&[1u8][..]), Err(InstructionError::UnknownMetadata(1)));
+
+        let mut bad = encoded.clone();
+        bad[1] = 0;
+        assert_eq!(WithdrawInst::try_from(bad.as_slice()), Err(InstructionError::BadShard(0)));
+
+        let mut bad = encoded.clone();
+        bad[2] = 65;
+        assert_eq!(WithdrawInst::try_from(bad.as_slice()), Err(InstructionError::BadTokenLen(65)));
+
+        let mut bad = encoded;
+        bad[3 + 64 + 1 + 63] = 0xff;
+        assert_eq!(WithdrawInst::try_from(bad.as_slice()), Err(InstructionError::NonUtf8Receiver));
+
+        let mut swap = SwapBeaconInst { prev_height: 1, height: 2, beacons: vec![[1u8; 64]] }.encode();
+        swap.truncate(SWAP_COMMITTEE_INST_LEN + 10);
+        assert_eq!(
+            SwapBeaconInst::try_from(swap.as_slice()),
+            Err(InstructionError::BadLength { expected: SWAP_COMMITTEE_INST_LEN + 64, actual: SWAP_COMMITTEE_INST_LEN + 10 })
+        );
+    }
+}
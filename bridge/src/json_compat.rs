@@ -0,0 +1,78 @@
+use std::convert::TryFrom;
+use std::fmt;
+use near_sdk::serde::de::{self, Visitor};
+use near_sdk::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// u128 in the v2 JSON interface, serialized as a decimal string like U128
+///
+/// JSON numbers are still accepted while the numeric interface is deprecated,
+/// JavaScript clients lose precision on numbers above 2^53
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct U128Compat(#[serde(with = "u128_compat")] pub u128);
+
+impl From<u128> for U128Compat {
+    fn from(value: u128) -> Self {
+        U128Compat(value)
+    }
+}
+
+impl From<U128Compat> for u128 {
+    fn from(value: U128Compat) -> Self {
+        value.0
+    }
+}
+
+/// serde helpers for u128 fields, `#[serde(with = "crate::json_compat::u128_compat")]`
+pub mod u128_compat {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        deserializer.deserialize_any(U128Visitor)
+    }
+}
+
+struct U128Visitor;
+
+impl<'de> Visitor<'de> for U128Visitor {
+    type Value = u128;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a u128 as decimal string or, deprecated, as number")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u128, E> {
+        value.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u128, E> {
+        Ok(value as u128)
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<u128, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u128, E> {
+        u128::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json;
+
+    #[test]
+    fn test_accepts_string_and_number() {
+        assert_eq!(serde_json::from_str::<U128Compat>("\"340282366920938463463374607431768211455\"").unwrap().0, u128::MAX);
+        assert_eq!(serde_json::from_str::<U128Compat>("42").unwrap().0, 42);
+        assert!(serde_json::from_str::<U128Compat>("-1").is_err());
+        assert!(serde_json::from_str::<U128Compat>("\"0x10\"").is_err());
+        assert_eq!(serde_json::to_string(&U128Compat(42)).unwrap(), "\"42\"");
+    }
+}
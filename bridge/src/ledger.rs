@@ -0,0 +1,160 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Gas, Promise, PromiseResult};
+
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::utils::NEAR_ADDRESS;
+use crate::*;
+
+impl Vault {
+    /// add shielded amount in token decimals to the ledger
+    ///
+    /// return false if the locked amount in incognito units would exceed u64,
+    /// `unit` is the token amount of one incognito unit
+    pub(crate) fn lock_balance(&mut self, token: &str, amount: u128, unit: u128) -> bool {
+        let key = token.to_string();
+        let locked = self.locked_balances.get(&key).unwrap_or_default().saturating_add(amount);
+        if locked / unit > u64::MAX as u128 {
+            return false;
+        }
+        self.locked_balances.insert(&key, &locked);
+
+        true
+    }
+
+    /// deduct paid amount in incognito decimals (9) from the ledger and add it to total unshielded
+    ///
+    /// saturates as deposits from before the ledger was introduced are not in it
+    /// until seeded by set_locked_balance
+    pub(crate) fn release_balance(&mut self, token: &str, amount: u128) {
+        let key = token.to_string();
+        let locked = self.locked_balances.get(&key).unwrap_or_default();
+        self.locked_balances.insert(&key, &locked.saturating_sub(self.to_token_amount(token, amount)));
+        let unshielded = self.total_unshielded.get(&key).unwrap_or_default();
+        self.total_unshielded.insert(&key, &unshielded.saturating_add(amount));
+    }
+
+    /// set ledger of token to amount in token decimals, executed through timelock
+    ///
+    /// seeds balances bridged before the ledger was introduced
+    pub(crate) fn set_locked_balance(&mut self, token: String, amount: U128) {
+        self.locked_balances.insert(&token, &amount.0);
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// compare ledger of fungible token with the vault balance on the token contract
+    ///
+    /// emits balance_discrepancy if they differ, callable by anyone
+    pub fn reconcile_balance(&self, token: AccountId) -> Promise {
+        if token.as_str() == NEAR_ADDRESS {
+            BridgeError::InvalidTokenAccount.panic();
+        }
+        ext_ft::ft_balance_of(
+            env::current_account_id(),
+            token.clone(),
+            0,
+            Gas(5_000_000_000_000),          // gas to attach
+        )
+        .then(ext_self::resolve_reconcile_balance(
+            token,
+            env::current_account_id(),
+            0,
+            Gas(5_000_000_000_000),          // gas to attach to the callback
+        ))
+    }
+
+    // getters
+
+    /// get bridged amount of token held by the vault in token decimals
+    pub fn get_locked_balance(&self, token: String) -> U128 {
+        U128(self.locked_balances.get(&token).unwrap_or_default())
+    }
+
+    // fallbacks
+
+    /// return true if vault balance on the token contract matches the ledger
+    #[private]
+    pub fn resolve_reconcile_balance(&self, token: AccountId) -> bool {
+        if env::promise_results_count() != 1 {
+            BridgeError::CallbackOnly.panic();
+        }
+
+        let balance = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => BridgeError::PromiseFailed.panic(),
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
+                .unwrap_or_else(|_| BridgeError::InvalidPromiseResult.panic()),
+        };
+        let locked = self.get_locked_balance(token.to_string());
+        if balance == locked {
+            return true;
+        }
+        BridgeEvent::BalanceDiscrepancy {
+            token: &token,
+            locked,
+            balance,
+        }.emit();
+
+        false
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{callback_results, json_result, new_vault};
+    use crate::timelock::ProposalAction;
+
+    fn balance_result(balance: u128) {
+        callback_results(vec![json_result(&U128(balance))]);
+    }
+
+    fn setup_vault() -> Vault {
+        let mut vault = new_vault();
+        vault.token_decimals.insert(&accounts(3).to_string(), &12);
+        vault
+    }
+
+    #[test]
+    fn test_lock_and_release() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        assert!(vault.lock_balance(&token, 5_000, 1_000));
+        vault.release_balance(&token, 2);
+        assert_eq!(vault.get_locked_balance(token.clone()).0, 3_000);
+        // release beyond ledger saturates
+        vault.release_balance(&token, 10);
+        assert_eq!(vault.get_locked_balance(token).0, 0);
+    }
+
+    #[test]
+    fn test_lock_over_cap() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        assert!(vault.lock_balance(&token, u64::MAX as u128, 1));
+        assert!(!vault.lock_balance(&token, 1, 1));
+        assert_eq!(vault.get_locked_balance(token).0, u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_seed_locked_balance() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        let id = vault.propose(ProposalAction::SetLockedBalance { token: token.clone(), amount: U128(7_000) });
+        vault.execute_proposal(id);
+        assert_eq!(vault.get_locked_balance(token).0, 7_000);
+    }
+
+    #[test]
+    fn test_reconcile_balance() {
+        let mut vault = setup_vault();
+        vault.lock_balance(&accounts(3).to_string(), 100, 1);
+        balance_result(100);
+        assert!(vault.resolve_reconcile_balance(accounts(3)));
+        balance_result(90);
+        assert!(!vault.resolve_reconcile_balance(accounts(3)));
+    }
+}
@@ -6,23 +6,51 @@ NOTES:
 */
 
 mod token_receiver;
+mod admin;
+mod batch;
+mod beacons;
+mod borsh_request;
+mod delayed_withdrawal;
 mod errors;
+mod events;
+pub mod instructions;
+pub mod json_compat;
+mod ledger;
+mod migration;
+mod rate_limit;
+mod relayer_fee;
+mod reserves;
+mod storage_pool;
+mod timelock;
+mod token_registry;
+mod upgrade;
+mod withdraw_call;
 mod utils;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test_utils;
 
 use std::str;
-use std::cmp::Ordering;
 use std::convert::{TryFrom, TryInto};
 use near_sdk::{serde_json};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::{env, near_bindgen, BorshStorageKey, PanicOnDefault, ext_contract, PromiseResult, AccountId, Gas, Promise, PromiseOrValue};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::collections::{LookupMap, TreeMap};
-use crate::errors::*;
-use crate::utils::{NEAR_ADDRESS, WITHDRAW_INST_LEN, SWAP_COMMITTEE_INST_LEN, WITHDRAW_METADATA, SWAP_BEACON_METADATA, BURN_METADATA};
-use crate::utils::{verify_inst};
-use arrayref::{array_refs, array_ref};
-use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
-use near_sdk::json_types::U128;
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use crate::admin::{Feature, Role};
+use crate::beacons::{decode_beacon_key, BeaconKey};
+use crate::borsh_request::BorshInteractRequest;
+use crate::delayed_withdrawal::WithdrawalDelay;
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::rate_limit::{RateLimit, RateLimitUsage};
+use crate::relayer_fee::{RelayerCredit, RelayerFee};
+use crate::timelock::Proposal;
+use crate::token_registry::TokenConfig;
+use crate::json_compat::U128Compat;
+use crate::instructions::{decode_hex, Instruction, InstructionError, WithdrawInst, WithdrawCallInst, BurnInst, SwapBeaconInst};
+use crate::utils::{NEAR_ADDRESS, STORAGE_RECORD_OVERHEAD};
+use crate::utils::{verify_inst, verify_proof};
+use near_sdk::json_types::{U128, U64};
 
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
@@ -30,7 +58,8 @@ use near_sdk::json_types::U128;
 pub struct InteractRequest {
     // instruction in bytes
     pub inst: String,
-    // beacon height
+    // beacon height, decimal string or deprecated number in JSON
+    #[serde(with = "crate::json_compat::u128_compat")]
     pub height: u128,
     // inst paths to build merkle tree
     pub inst_paths: Vec<[u8; 32]>,
@@ -48,13 +77,116 @@ pub struct InteractRequest {
     pub vs: Vec<u8>
 }
 
+/// Withdraw or withdraw call instruction decoded from a burn proof.
+pub(crate) struct Unshield {
+    pub token: String,
+    pub receiver: AccountId,
+    // amount in incognito decimals (9)
+    pub amount: u128,
+    pub tx_id: [u8; 32],
+    // message for ft_transfer_call, None for plain transfer
+    pub msg: Option<String>,
+}
+
+impl Unshield {
+    /// decode withdraw or withdraw call instruction
+    pub(crate) fn decode(inst: &[u8]) -> Result<Self, BridgeError> {
+        let (token, receiver, amount, tx_id, msg) = match Instruction::try_from(inst)? {
+            Instruction::Withdraw(WithdrawInst { token, receiver, amount, tx_id }) =>
+                (token, receiver, amount, tx_id, None),
+            Instruction::WithdrawCall(WithdrawCallInst { token, receiver, amount, tx_id, msg }) =>
+                (token, receiver, amount, tx_id, Some(msg)),
+            _ => return Err(InstructionError::UnknownMetadata(inst[0]).into()),
+        };
+        let receiver: AccountId = receiver.try_into().map_err(|_| BridgeError::InvalidReceiverAccount)?;
+
+        Ok(Unshield { token, receiver, amount: u128::from(amount), tx_id, msg })
+    }
+}
+
+/// Withdrawal handed to payout callbacks.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawalPayout {
+    // burn tx id of the withdrawal
+    pub tx_id: [u8; 32],
+    // account to receive token
+    pub receiver: AccountId,
+    // token address, NEAR_ADDRESS for native token
+    pub token: String,
+    // amount in incognito decimals (9)
+    pub amount: U128,
+    // message for ft_transfer_call, None for plain transfer
+    pub msg: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FailedWithdrawal {
+    // account to receive token
+    pub receiver: AccountId,
+    // token address, NEAR_ADDRESS for native token
+    pub token: String,
+    // amount in incognito decimals (9)
+    pub amount: U128,
+    // message for ft_transfer_call, replayed by retry_withdrawal
+    pub msg: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingWithdrawal {
+    // account to receive token
+    pub receiver: AccountId,
+    // token address, NEAR_ADDRESS for native token
+    pub token: String,
+    // amount in incognito decimals (9)
+    pub amount: U128,
+    // block timestamp withdrawal was queued at
+    pub queued_at: U64,
+    // block timestamp withdrawal can be finalized from
+    pub unlock_at: U64,
+    // message for ft_transfer_call, None for plain transfer
+    pub msg: Option<String>,
+    // relayer fee credited on finalize, dropped on veto
+    pub relayer_fee: Option<RelayerCredit>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositReceipt {
+    // token address, NEAR_ADDRESS for native token
+    pub token: String,
+    // amount in incognito decimals (9)
+    pub amount: U128,
+    // incognito payment address to mint token to
+    pub incognito_address: String,
+    // block height deposit accepted at
+    pub block_height: U64,
+}
+
 #[derive(BorshStorageKey, BorshSerialize)]
 pub(crate) enum StorageKey {
     Transaction,
+    // hex committees of v0 and v1 state, see Vault::legacy_beacons
     BeaconHeight,
     TokenAccountID,
     TokenUserAccountID,
     TokenDecimals,
+    FailedWithdrawal,
+    DepositReceipt,
+    Roles,
+    Proposals,
+    RateLimits,
+    RateLimitUsage,
+    PendingWithdrawals,
+    WithdrawalDelays,
+    Tokens,
+    LockedBalances,
+    TotalUnshielded,
+    RelayerFees,
+    BeaconKeys,
+    BeaconIndex,
 }
 
 #[near_bindgen]
@@ -62,54 +194,150 @@ pub(crate) enum StorageKey {
 pub struct Vault {
     // mark tx already burn
     pub tx_burn: LookupMap<[u8; 32], bool>,
-    // beacon committees
-    pub beacons: TreeMap<u128, Vec<String>>,
+    // beacon committees by height they sign from
+    pub beacons: TreeMap<u128, Vec<BeaconKey>>,
+    // latest committee height by keccak256 of beacon key
+    pub beacon_index: LookupMap<[u8; 32], u128>,
     // total withdraw request
     pub total_credit_amount: LookupMap<String, u128>,
     // total credit amount for each account
     pub credit_amount: LookupMap<(String, String), u128>,
-    // store token decimal
+    // token decimals, cached on token registration
     pub token_decimals: LookupMap<String, u8>,
+    // withdrawals whose transfer failed, by burn tx id
+    pub failed_withdrawals: LookupMap<[u8; 32], FailedWithdrawal>,
+    // next deposit nonce
+    pub deposit_nonce: u64,
+    // accepted shield requests by nonce
+    pub deposit_receipts: LookupMap<u64, DepositReceipt>,
+    // vault owner, grants roles and unpauses
+    pub owner_id: AccountId,
+    // roles granted to accounts
+    pub roles: UnorderedSet<(Role, AccountId)>,
+    // bitmask of paused features
+    pub paused: u8,
+    // version of this layout, see migration::STATE_VERSION
+    pub state_version: u32,
+    // sha256 hash of code authorized for upgrade by beacon committee
+    pub upgrade_code_hash: Option<[u8; 32]>,
+    // delay in nanoseconds before proposals can be executed
+    pub timelock_delay: u64,
+    // pending admin proposals by id
+    pub proposals: UnorderedMap<u64, Proposal>,
+    // id of the next proposal
+    pub next_proposal_id: u64,
+    // withdrawal rate limit by token
+    pub rate_limits: LookupMap<String, RateLimit>,
+    // amount released in rate limit window by token
+    pub rate_limit_usage: LookupMap<String, RateLimitUsage>,
+    // withdrawals queued by delay or rate limit, by burn tx id
+    pub pending_withdrawals: UnorderedMap<[u8; 32], PendingWithdrawal>,
+    // delay for large withdrawals by token
+    pub withdrawal_delays: LookupMap<String, WithdrawalDelay>,
+    // fungible tokens accepted for shielding
+    pub tokens: UnorderedMap<String, TokenConfig>,
+    // ledger of bridged amount held by the vault in token decimals, by token
+    pub locked_balances: LookupMap<String, u128>,
+    // amount paid out by withdraw and claim_credit in incognito decimals (9), by token
+    pub total_unshielded: LookupMap<String, u128>,
+    // yocto NEAR paying storage_deposit of withdrawal receivers
+    pub storage_pool: u128,
+    // fee schedule of relayers submitting withdraw proofs, by token
+    pub relayer_fees: LookupMap<String, RelayerFee>,
+    // hex committees of v0 and v1 state, read for heights before the first committee in beacons
+    pub legacy_beacons: TreeMap<u128, Vec<String>>,
 }
 
 // define the methods we'll use on ContractB
 #[ext_contract(ext_ft)]
 pub trait FtContract {
-    fn ft_metadata(&self) -> FungibleTokenMetadata;
     fn ft_balance_of(&mut self, account_id: AccountId) -> U128;
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> U128;
 }
 
 // define methods we'll use as callbacks on ContractA
 #[ext_contract(ext_self)]
 pub trait VaultContract {
-    fn fallback_deposit(
+    fn resolve_claim_credit(
+        &mut self,
+        token: String,
+        account_id: AccountId,
+        amount: U128,
+    ) -> bool;
+    fn resolve_withdraw(
+        &mut self,
+        tx_id: [u8; 32],
+        receiver: AccountId,
+        token: String,
+        amount: U128,
+    ) -> bool;
+    fn resolve_withdraw_call(
+        &mut self,
+        payout: WithdrawalPayout,
+    ) -> bool;
+    fn resolve_storage_and_pay(
+        &mut self,
+        payout: WithdrawalPayout,
+    ) -> Promise;
+    fn resolve_storage_deposit(
+        &mut self,
+        deposit: U128,
+    );
+    fn resolve_reconcile_balance(
         &self,
-        incognito_address: String,
         token: AccountId,
-        amount: u128,
-    );
+    ) -> bool;
 }
 
 #[near_bindgen]
 impl Vault {
-    /// Initializes the beacon list
+    /// Initializes the beacon list, caller becomes owner
     #[init]
     pub fn new(
         beacons: Vec<String>,
-        height: u128,
+        height: U128Compat,
     ) -> Self {
-        assert!(!env::state_exists(), "Already initialized");
-        assert!(!beacons.len().eq(&0), "Invalid beacon list");
+        if env::state_exists() {
+            BridgeError::AlreadyInitialized.panic();
+        }
+        let beacons: Vec<BeaconKey> = beacons.iter()
+            .map(|key| decode_beacon_key(key).unwrap_or_else(|| BridgeError::InvalidBeaconList.panic()))
+            .collect();
+        if beacons.is_empty() {
+            BridgeError::InvalidBeaconList.panic();
+        }
         let mut this = Self {
             tx_burn: LookupMap::new(StorageKey::Transaction), 
-            beacons: TreeMap::new(StorageKey::BeaconHeight),
+            beacons: TreeMap::new(StorageKey::BeaconKeys),
+            beacon_index: LookupMap::new(StorageKey::BeaconIndex),
             total_credit_amount: LookupMap::new(StorageKey::TokenAccountID),
             credit_amount: LookupMap::new(StorageKey::TokenUserAccountID),
             token_decimals: LookupMap::new(StorageKey::TokenDecimals),
+            failed_withdrawals: LookupMap::new(StorageKey::FailedWithdrawal),
+            deposit_nonce: 0,
+            deposit_receipts: LookupMap::new(StorageKey::DepositReceipt),
+            owner_id: env::predecessor_account_id(),
+            roles: UnorderedSet::new(StorageKey::Roles),
+            paused: 0,
+            state_version: migration::STATE_VERSION,
+            upgrade_code_hash: None,
+            timelock_delay: 0,
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            next_proposal_id: 0,
+            rate_limits: LookupMap::new(StorageKey::RateLimits),
+            rate_limit_usage: LookupMap::new(StorageKey::RateLimitUsage),
+            pending_withdrawals: UnorderedMap::new(StorageKey::PendingWithdrawals),
+            withdrawal_delays: LookupMap::new(StorageKey::WithdrawalDelays),
+            tokens: UnorderedMap::new(StorageKey::Tokens),
+            locked_balances: LookupMap::new(StorageKey::LockedBalances),
+            total_unshielded: LookupMap::new(StorageKey::TotalUnshielded),
+            storage_pool: 0,
+            relayer_fees: LookupMap::new(StorageKey::RelayerFees),
+            legacy_beacons: TreeMap::new(StorageKey::BeaconHeight),
         };
         // insert beacon height and list in tree
-        this.beacons.insert(&height, &beacons);
+        this.insert_committee(height.0, &beacons);
 
         this
     }
@@ -118,127 +346,338 @@ impl Vault {
     ///
     /// receive token from users and generate proof
     /// validate proof on Incognito side and mint corresponding token
+    /// storage of the deposit receipt is paid from the attached deposit,
+    /// remainder below one incognito unit (1e15 yocto) is refunded to caller
     #[payable]
     pub fn deposit(
         &mut self,
         incognito_address: String,
     ) {
-        let total_native = env::account_balance();
-        if total_native.checked_div(1e15 as u128).unwrap_or_default().cmp(&(u64::MAX as u128)) == Ordering::Greater {
-            panic!("{}", VALUE_EXCEEDED);
-        }
+        self.assert_not_paused(Feature::Deposit);
 
         // extract near amount from deposit transaction
-        let amount = env::attached_deposit().checked_div(1e15 as u128).unwrap_or(0);
+        let attached = env::attached_deposit()
+            .checked_sub(Self::receipt_storage_cost(NEAR_ADDRESS, &incognito_address))
+            .unwrap_or_else(|| BridgeError::DepositTooSmall.panic());
+        let amount = attached / 1e15 as u128;
+        let dust = attached % 1e15 as u128;
+        if amount == 0 {
+            BridgeError::DepositTooSmall.panic();
+        }
+
+        // cap bridged total, account balance also holds storage stake
+        if !self.lock_balance(NEAR_ADDRESS, attached - dust, 1e15 as u128) {
+            BridgeError::ValueExceeded.panic();
+        }
+
+        self.record_deposit(NEAR_ADDRESS.to_string(), amount, incognito_address);
+        if dust > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(dust);
+        }
+    }
+
+    /// yocto NEAR staked for the receipt record_deposit stores
+    pub(crate) fn receipt_storage_cost(token: &str, incognito_address: &str) -> u128 {
+        let receipt = DepositReceipt {
+            token: token.to_string(),
+            amount: U128(0),
+            incognito_address: incognito_address.to_string(),
+            block_height: U64(0),
+        };
+        // key is the map prefix and the nonce
+        let key_len = StorageKey::DepositReceipt.try_to_vec().unwrap_or_default().len() + 8;
+        let bytes = key_len + receipt.try_to_vec().unwrap_or_default().len() + STORAGE_RECORD_OVERHEAD;
+        bytes as u128 * env::storage_byte_cost()
+    }
+
+    /// store deposit receipt under next nonce and emit shield event, return the nonce
+    fn record_deposit(
+        &mut self,
+        token: String,
+        amount: u128,
+        incognito_address: String,
+    ) -> u64 {
+        let nonce = self.deposit_nonce;
+        BridgeEvent::Shield {
+            token: &token,
+            amount: U128(amount),
+            incognito_address: &incognito_address,
+            nonce: U64(nonce),
+        }.emit();
+        #[cfg(feature = "legacy-log")]
         env::log_str(format!(
             "{} {} {}",
-            incognito_address, NEAR_ADDRESS.to_string(), amount
+            incognito_address, token, amount
         ).as_str());
+
+        self.deposit_receipts.insert(&nonce, &DepositReceipt {
+            token,
+            amount: U128(amount),
+            incognito_address,
+            block_height: U64(env::block_height()),
+        });
+        self.deposit_nonce = nonce.checked_add(1).unwrap_or_else(|| BridgeError::AmountOverflow.panic());
+
+        nonce
     }
 
     /// withdraw tokens
     ///
     /// submit burn proof to receive token
+    /// large withdrawal or withdrawal beyond token rate limit is queued and false returned
+    /// caller other than the receiver is credited the relayer fee of the token when it is paid
     pub fn withdraw(
         &mut self,
         unshield_info: InteractRequest
-    ) -> Promise {
-        let beacons = self.get_beacons(unshield_info.height);
+    ) -> PromiseOrValue<bool> {
+        let proof = BorshInteractRequest::try_from(&unshield_info)
+            .unwrap_or_else(|e| e.panic());
+        self.withdraw_proof(proof)
+    }
+
+    fn withdraw_proof(
+        &mut self,
+        unshield_info: BorshInteractRequest
+    ) -> PromiseOrValue<bool> {
+        self.assert_not_paused(Feature::Withdraw);
+        let beacons = self.committee(unshield_info.height);
 
         // verify instruction
-        verify_inst(&unshield_info, beacons);
+        verify_proof(&unshield_info, beacons);
 
         // parse instruction
-        let inst = hex::decode(unshield_info.inst).unwrap_or_default();
-        let inst_ = array_ref![inst, 0, WITHDRAW_INST_LEN];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (meta_type, shard_id, token_len, token, receiver_len, receiver_key, _, unshield_amount, tx_id) =
-            array_refs![inst_, 1, 1, 1, 64, 1, 64, 24, 8, 32];
-        let meta_type = u8::from_be_bytes(*meta_type);
-        let shard_id = u8::from_be_bytes(*shard_id);
-        let mut unshield_amount = u128::from(u64::from_be_bytes(*unshield_amount));
-        let token_len = u8::from_be_bytes(*token_len);
-        let receiver_len = u8::from_be_bytes(*receiver_len);
-        let token = &token[64 - token_len as usize..];
-        let token: String = String::from_utf8(token.to_vec()).unwrap_or_default();
-        let receiver_key = &receiver_key[64 - receiver_len as usize..];
-        let receiver_key: String = String::from_utf8(receiver_key.to_vec()).unwrap_or_default();
-
-        // validate metatype and key provided
-        if (meta_type != WITHDRAW_METADATA) || shard_id != 1 {
-            panic!("{}", INVALID_METADATA);
+        let unshield = Unshield::decode(&unshield_info.inst)
+            .unwrap_or_else(|e| e.panic());
+
+        // check tx burn used
+        if self.tx_burn.get(&unshield.tx_id).unwrap_or_default() {
+            BridgeError::InvalidTxBurn.panic();
+        }
+        self.execute_unshield(unshield)
+    }
+
+    /// mark burn tx used, then pay, or queue if delayed or rate limited
+    fn execute_unshield(
+        &mut self,
+        unshield: Unshield,
+    ) -> PromiseOrValue<bool> {
+        let Unshield { token, receiver: account, amount: unshield_amount, tx_id, msg } = unshield;
+        self.assert_payable(&token, unshield_amount);
+        self.tx_burn.insert(&tx_id, &true);
+
+        // relayer submitting the proof for the receiver earns the token's relayer fee
+        let relayer = env::predecessor_account_id();
+        let relayer_fee = if relayer != account { self.relayer_fee_of(&token, unshield_amount) } else { 0 };
+        BridgeEvent::Unshield {
+            tx_id: hex::encode(tx_id),
+            receiver: &account,
+            token: &token,
+            amount: U128(unshield_amount),
+            relayer: &relayer,
+            relayer_fee: U128(relayer_fee),
+        }.emit();
+        let unshield_amount = unshield_amount - relayer_fee;
+        let relayer_fee = Some(RelayerCredit { relayer, fee: U128(relayer_fee) })
+            .filter(|credit| credit.fee.0 > 0);
+
+        // rate limit of delayed withdrawal is consumed in finalize_withdrawal
+        let now = env::block_timestamp();
+        let unlock_at = self.withdrawal_unlock_at(&token, unshield_amount);
+        if unlock_at > now || !self.consume_rate_limit(&token, unshield_amount) {
+            BridgeEvent::WithdrawalQueued {
+                tx_id: hex::encode(tx_id),
+                receiver: &account,
+                token: &token,
+                amount: U128(unshield_amount),
+                unlock_at: U64(unlock_at),
+            }.emit();
+            self.pending_withdrawals.insert(&tx_id, &PendingWithdrawal {
+                receiver: account,
+                token,
+                amount: U128(unshield_amount),
+                queued_at: U64(now),
+                unlock_at: U64(unlock_at),
+                msg,
+                relayer_fee,
+            });
+            return PromiseOrValue::Value(false);
         }
+        self.credit_relayer(&token, relayer_fee);
+        self.pay_withdrawal(tx_id, account, token, unshield_amount, msg).into()
+    }
+
+    /// pay withdrawal, fungible token receiver is registered on the token first if needed
+    ///
+    /// see resolve_storage_and_pay
+    fn pay_withdrawal(
+        &self,
+        tx_id: [u8; 32],
+        account: AccountId,
+        token: String,
+        amount: u128,
+        msg: Option<String>,
+    ) -> Promise {
+        if token == NEAR_ADDRESS {
+            return self.transfer_withdrawal(None, tx_id, account, token, amount, None);
+        }
+        self.check_storage_and_pay(tx_id, account, token, amount, msg)
+    }
+
+    /// transfer withdrawal after `before`, recorded as failed in resolve_withdraw if transfer fails
+    ///
+    /// fungible token withdrawal with msg is paid through ft_transfer_call,
+    /// msg is ignored for native token
+    fn transfer_withdrawal(
+        &self,
+        before: Option<Promise>,
+        tx_id: [u8; 32],
+        account: AccountId,
+        token: String,
+        amount: u128,
+        msg: Option<String>,
+    ) -> Promise {
+        let (transfer, resolve) = match msg.filter(|_| token != NEAR_ADDRESS) {
+            Some(msg) => self.withdraw_call_promises(tx_id, account, token, amount, msg),
+            None => (
+                self.transfer_token(account.clone(), token.clone(), amount),
+                ext_self::resolve_withdraw(
+                    tx_id,
+                    account,
+                    token,
+                    U128(amount),
+                    env::current_account_id(),
+                    0,
+                    Gas(10_000_000_000_000),         // gas to attach to the callback
+                ),
+            ),
+        };
+        match before {
+            Some(before) => before.then(transfer).then(resolve),
+            None => transfer.then(resolve),
+        }
+    }
+
+    /// retry failed withdrawal
+    ///
+    /// anyone can retry once the receiver is able to accept the token
+    pub fn retry_withdrawal(
+        &mut self,
+        tx_id: [u8; 32],
+    ) -> Promise {
+        self.assert_not_paused(Feature::Withdraw);
+        let failed = self.failed_withdrawals.remove(&tx_id)
+            .unwrap_or_else(|| BridgeError::FailedWithdrawalNotFound.panic());
+        self.assert_payable(&failed.token, failed.amount.0);
+        // rate limit was consumed by the first attempt
+        self.pay_withdrawal(tx_id, failed.receiver, failed.token, failed.amount.0, failed.msg)
+    }
+
+    /// claim failed withdrawal
+    ///
+    /// receiver moves failed withdrawal to its credit, spendable by claim_credit
+    pub fn claim_failed_withdrawal(
+        &mut self,
+        tx_id: [u8; 32],
+    ) {
+        self.assert_not_paused(Feature::ClaimCredit);
+        let failed = self.failed_withdrawals.get(&tx_id)
+            .unwrap_or_else(|| BridgeError::FailedWithdrawalNotFound.panic());
+        if failed.receiver != env::predecessor_account_id() {
+            BridgeError::NotWithdrawalReceiver.panic();
+        }
+        self.failed_withdrawals.remove(&tx_id);
+
+        // rate limit is consumed again by claim_credit
+        self.release_rate_limit(&failed.token, failed.amount.0);
+        self.add_credit(&failed.token, &failed.receiver, failed.amount.0);
+    }
+
+    /// credit burned amount to receiver, keyed by token and account id as claim_credit reads them
+    fn credit_burn(&mut self, inst: BurnInst) {
+        let BurnInst { token, receiver, amount, tx_id } = inst;
 
         // check tx burn used
         if self.tx_burn.get(&tx_id).unwrap_or_default() {
-            panic!("{}", INVALID_TX_BURN);
+            BridgeError::InvalidTxBurn.panic();
         }
         self.tx_burn.insert(&tx_id, &true);
 
-        let account: AccountId = receiver_key.try_into().unwrap();
-        if token == NEAR_ADDRESS {
-            unshield_amount = unshield_amount.checked_mul(1e15 as u128).unwrap();
-            Promise::new(account).transfer(unshield_amount)
-        } else {
-            let decimals = self.token_decimals.get(&token).unwrap();
-            if decimals > 9 {
-                unshield_amount = unshield_amount.checked_mul(u128::pow(10, decimals as u32 - 9)).unwrap()
-            }
-            let token: AccountId = token.try_into().unwrap();
-            ext_ft::ft_transfer(
-                account,
-                U128(unshield_amount),
-                None,
-                token,
-                1,
-                Gas(5_000_000_000_000),
-            ).into()
+        if token != NEAR_ADDRESS && AccountId::try_from(token.clone()).is_err() {
+            BridgeError::InvalidTokenAccount.panic();
+        }
+        let account: AccountId = AccountId::try_from(receiver)
+            .unwrap_or_else(|_| BridgeError::InvalidReceiverAccount.panic());
+
+        self.add_credit(&token, &account, u128::from(amount));
+        BridgeEvent::BurnProofCredit {
+            tx_id: hex::encode(tx_id),
+            account: account.as_str(),
+            token: &token,
+            amount: U128(u128::from(amount)),
+        }.emit();
+    }
+
+    /// credit relayer fee of a withdrawal being paid
+    fn credit_relayer(&mut self, token: &str, relayer_fee: Option<RelayerCredit>) {
+        if let Some(RelayerCredit { relayer, fee }) = relayer_fee {
+            self.add_credit(token, &relayer, fee.0);
         }
     }
 
+    /// add amount in incognito decimals (9) to credit of account, spendable by claim_credit
+    fn add_credit(&mut self, token: &str, account: &AccountId, amount: u128) {
+        let key = (token.to_string(), account.to_string());
+        let credit = self.credit_amount.get(&key).unwrap_or_default();
+        self.credit_amount.insert(&key, &(credit + amount));
+        let total = self.total_credit_amount.get(&key.0).unwrap_or_default();
+        self.total_credit_amount.insert(&key.0, &(total + amount));
+    }
+
     /// swap beacon committee
     ///
     /// verify old beacon committee's signature and update new beacon committee
+    ///
+    /// instruction carries 64 byte keys, see instructions::SwapBeaconInst
     pub fn swap_beacon_committee(
         &mut self,
         swap_info: InteractRequest
     ) -> bool {
-        let beacons = self.get_beacons(swap_info.height);
+        let proof = BorshInteractRequest::try_from(&swap_info)
+            .unwrap_or_else(|e| e.panic());
+        self.swap_beacon_committee_proof(proof)
+    }
+
+    fn swap_beacon_committee_proof(
+        &mut self,
+        swap_info: BorshInteractRequest
+    ) -> bool {
+        self.assert_not_paused(Feature::SwapBeaconCommittee);
+        let beacons = self.committee(swap_info.height);
 
         // verify instruction
-        verify_inst(&swap_info, beacons);
+        verify_proof(&swap_info, beacons);
 
         // parse instruction
-        let inst = hex::decode(swap_info.inst).unwrap_or_default();
-        let inst_ = array_ref![inst, 0, SWAP_COMMITTEE_INST_LEN];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (meta_type, shard_id, _, prev_height, _, height, _, num_vals) =
-            array_refs![inst_, 1, 1, 16, 16, 16, 16, 16, 16];
-        let meta_type = u8::from_be_bytes(*meta_type);
-        let shard_id = u8::from_be_bytes(*shard_id);
-        let prev_height = u128::from_be_bytes(*prev_height);
-        let height = u128::from_be_bytes(*height);
-        let num_vals = u128::from_be_bytes(*num_vals);
-
-        let mut beacons: Vec<String> = vec![];
-        for i in 0..num_vals {
-            let index = i as usize;
-            let beacon_key = array_ref![inst, SWAP_COMMITTEE_INST_LEN + index * 32, 32];
-            let beacon = hex::encode(beacon_key);
-            beacons.push(beacon);
-        }
-
-        // validate metatype and key provided
-        if meta_type != SWAP_BEACON_METADATA || shard_id != 1 {
-            panic!("{}", INVALID_METADATA);
-        }
+        let inst = SwapBeaconInst::try_from(swap_info.inst.as_slice())
+            .unwrap_or_else(|e| BridgeError::from(e).panic());
+        let SwapBeaconInst { prev_height, height, beacons } = inst;
+        let num_vals = beacons.len() as u128;
 
         let my_latest_commitee_height = self.beacons.max().unwrap_or_default();
-        assert!(prev_height.eq(&my_latest_commitee_height), "{}", PREV_COMMITTEE_HEIGHT_MISMATCH);
-        assert!(height > my_latest_commitee_height, "{}", COMMITTEE_HEIGHT_MISMATCH);
+        if !prev_height.eq(&my_latest_commitee_height) {
+            BridgeError::PrevCommitteeHeightMismatch.panic();
+        }
+        if height <= my_latest_commitee_height {
+            BridgeError::CommitteeHeightMismatch.panic();
+        }
 
         // swap committee
-        self.beacons.insert(&height, &beacons);
+        self.insert_committee(height, &beacons);
+        BridgeEvent::SwapBeaconCommittee {
+            prev_height: U128(prev_height),
+            height: U128(height),
+            num_vals: U128(num_vals),
+        }.emit();
 
         true
     }
@@ -251,55 +690,201 @@ impl Vault {
         &mut self,
         burn_info: InteractRequest
     ) {
-        let beacons = self.get_beacons(burn_info.height);
+        self.assert_not_paused(Feature::SubmitBurnProof);
+        let beacons = self.committee(burn_info.height);
 
         // verify instruction
         verify_inst(&burn_info, beacons);
 
         // parse instruction
-        let inst = hex::decode(burn_info.inst).unwrap_or_default();
-        let inst_ = array_ref![inst, 0, WITHDRAW_INST_LEN];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (meta_type, shard_id, token_len, token, receiver_len, receiver_key, _, burn_amount, tx_id) =
-            array_refs![inst_, 1, 1, 1, 64, 1, 64, 24, 8, 32];
-        let meta_type = u8::from_be_bytes(*meta_type);
-        let shard_id = u8::from_be_bytes(*shard_id);
-        let burn_amount = u128::from(u64::from_be_bytes(*burn_amount));
-        let token_len = u8::from_be_bytes(*token_len);
-        let receiver_len = u8::from_be_bytes(*receiver_len);
-        let token = &token[64 - token_len as usize..];
-        let token: String = String::from_utf8(token.to_vec()).unwrap_or_default();
-        let receiver_key = &receiver_key[64 - receiver_len as usize..];
-        let receiver_key: String = String::from_utf8(receiver_key.to_vec()).unwrap_or_default();
-
-        // validate metatype and key provided
-        if (meta_type != BURN_METADATA) || shard_id != 1 {
-            panic!("{}", INVALID_METADATA);
+        let inst = decode_hex(&burn_info.inst)
+            .and_then(|inst| BurnInst::try_from(inst.as_slice()))
+            .unwrap_or_else(|e| BridgeError::from(e).panic());
+        self.credit_burn(inst);
+    }
+
+
+
+    /// claim credit
+    ///
+    /// spend credit recorded by submit_burn_proof, amount is in incognito decimals (9)
+    pub fn claim_credit(
+        &mut self,
+        token: String,
+        amount: U128,
+    ) -> Promise {
+        self.assert_not_paused(Feature::ClaimCredit);
+        let account = env::predecessor_account_id();
+        let amount = amount.0;
+        if amount == 0 {
+            BridgeError::InvalidClaimAmount.panic();
         }
 
-        // check tx burn used
-        if self.tx_burn.get(&tx_id).unwrap_or_default() {
-            panic!("{}", INVALID_TX_BURN);
+        // spend credit before transfer, restored in resolve_claim_credit on failure
+        let key = (token.clone(), account.to_string());
+        let credit = self.credit_amount.get(&key).unwrap_or_default();
+        if credit < amount {
+            BridgeError::InsufficientCredit.panic();
         }
-        self.tx_burn.insert(&tx_id, &true);
+        if !self.consume_rate_limit(&token, amount) {
+            BridgeError::RateLimitExceeded.panic();
+        }
+        if credit == amount {
+            self.credit_amount.remove(&key);
+        } else {
+            self.credit_amount.insert(&key, &(credit - amount));
+        }
+        let total = self.total_credit_amount.get(&token).unwrap_or_default();
+        self.total_credit_amount.insert(&token, &(total - amount));
+        BridgeEvent::ClaimCredit {
+            account: &account,
+            token: &token,
+            amount: U128(amount),
+        }.emit();
+
+        self.transfer_token(account.clone(), token.clone(), amount)
+            .then(ext_self::resolve_claim_credit(
+                token,
+                account,
+                U128(amount),
+                env::current_account_id(),
+                0,
+                Gas(5_000_000_000_000),          // gas to attach to the callback
+            ))
+    }
 
-        let token: AccountId = AccountId::try_from(hex::encode(token)).unwrap();
-        let account: AccountId = AccountId::try_from(hex::encode(receiver_key)).unwrap();
+    /// move credit submit_burn_proof recorded under hex encoded token and account id
+    /// before state version 2 to the plain ids claim_credit reads
+    ///
+    /// anyone can recover it, credit only moves to its own account, return amount moved
+    pub fn recover_hex_credit(
+        &mut self,
+        token: String,
+        account_id: AccountId,
+    ) -> U128 {
+        let hex_token = hex::encode(&token);
+        let key = (hex_token.clone(), hex::encode(account_id.as_str()));
+        let amount = match self.credit_amount.remove(&key) {
+            Some(amount) => amount,
+            None => return U128(0),
+        };
+        let total = self.total_credit_amount.get(&hex_token).unwrap_or_default();
+        if total > amount {
+            self.total_credit_amount.insert(&hex_token, &(total - amount));
+        } else {
+            self.total_credit_amount.remove(&hex_token);
+        }
+        self.add_credit(&token, &account_id, amount);
+        BridgeEvent::HexCreditRecovered {
+            account: &account_id,
+            token: &token,
+            amount: U128(amount),
+        }.emit();
 
-        let amount = self.total_credit_amount.get(&token.to_string()).unwrap_or_default();
-        self.total_credit_amount.insert(&token.to_string(), &(amount + burn_amount));
-        let amount = self.credit_amount.get(&(token.to_string(), account.to_string())).unwrap_or_default();
-        self.credit_amount.insert(&(token.to_string(), account.to_string()), &(amount + burn_amount));
+        U128(amount)
     }
 
+    /// transfer native or fungible token, amount is in incognito decimals (9)
+    fn transfer_token(
+        &self,
+        account: AccountId,
+        token: String,
+        amount: u128,
+    ) -> Promise {
+        let amount = self.to_token_amount(&token, amount);
+        if token == NEAR_ADDRESS {
+            Promise::new(account).transfer(amount)
+        } else {
+            let token: AccountId = token.try_into()
+                .unwrap_or_else(|_| BridgeError::InvalidTokenAccount.panic());
+            ext_ft::ft_transfer(
+                account,
+                U128(amount),
+                None,
+                token,
+                1,
+                Gas(5_000_000_000_000),
+            )
+        }
+    }
 
+    /// check amount of token can be paid, callbacks paying it would otherwise fail
+    /// after the burn tx or queued withdrawal is already spent
+    pub(crate) fn check_payable(&self, token: &str, amount: u128) -> Result<(), BridgeError> {
+        if token != NEAR_ADDRESS {
+            AccountId::try_from(token.to_string()).map_err(|_| BridgeError::InvalidTokenAccount)?;
+        }
+        let unit = self.token_unit(token).ok_or(BridgeError::TokenDecimalsNotFound)?;
+        amount.checked_mul(unit).ok_or(BridgeError::AmountOverflow)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn assert_payable(&self, token: &str, amount: u128) {
+        self.check_payable(token, amount).unwrap_or_else(|e| e.panic());
+    }
+
+    /// scale amount in incognito decimals (9) to token decimals
+    fn to_token_amount(&self, token: &str, amount: u128) -> u128 {
+        let scale = self.token_unit(token)
+            .unwrap_or_else(|| BridgeError::TokenDecimalsNotFound.panic());
+        amount.checked_mul(scale)
+            .unwrap_or_else(|| BridgeError::AmountOverflow.panic())
+    }
+
+    /// token amount of one incognito unit, None if decimals are unknown
+    fn token_unit(&self, token: &str) -> Option<u128> {
+        if token == NEAR_ADDRESS {
+            return Some(1e15 as u128);
+        }
+        let decimals = self.token_decimals.get(&token.to_string())?;
+        Some(if decimals > 9 { u128::pow(10, decimals as u32 - 9) } else { 1 })
+    }
 
     /// getters
 
-    /// get beacon list by height
-    pub fn get_beacons(&self, height: u128) -> Vec<String> {
-        let get_height_key = self.beacons.lower(&(height + 1)).unwrap();
-        self.beacons.get(&get_height_key).unwrap()
+    /// get credit of account for token
+    pub fn get_credit(&self, token: String, account_id: AccountId) -> U128 {
+        U128(self.credit_amount.get(&(token, account_id.to_string())).unwrap_or_default())
+    }
+
+    /// get total unclaimed credit for token
+    pub fn get_total_credit(&self, token: String) -> U128 {
+        U128(self.total_credit_amount.get(&token).unwrap_or_default())
+    }
+
+    /// get hex encoded beacon list by height
+    pub fn get_beacons(&self, height: U128Compat) -> Vec<String> {
+        self.committee(height.0).iter().map(hex::encode).collect()
+    }
+
+    /// get deposit receipt by nonce
+    pub fn get_deposit_receipt(&self, nonce: U64) -> Option<DepositReceipt> {
+        self.deposit_receipts.get(&nonce.0)
+    }
+
+    /// get nonce the next deposit will be recorded under
+    pub fn get_deposit_nonce(&self) -> U64 {
+        U64(self.deposit_nonce)
+    }
+
+    /// get withdrawal queued by delay or rate limit by burn tx id
+    pub fn get_pending_withdrawal(&self, tx_id: [u8; 32]) -> Option<PendingWithdrawal> {
+        self.pending_withdrawals.get(&tx_id)
+    }
+
+    /// list withdrawals queued by delay or rate limit
+    pub fn get_pending_withdrawals(&self, from_index: U64, limit: U64) -> Vec<([u8; 32], PendingWithdrawal)> {
+        self.pending_withdrawals
+            .iter()
+            .skip(from_index.0 as usize)
+            .take(limit.0 as usize)
+            .collect()
+    }
+
+    /// get failed withdrawal by burn tx id
+    pub fn get_failed_withdrawal(&self, tx_id: [u8; 32]) -> Option<FailedWithdrawal> {
+        self.failed_withdrawals.get(&tx_id)
     }
 
     /// check tx burn used
@@ -308,55 +893,154 @@ impl Vault {
     }
 
     /// fallbacks
-    pub fn fallback_deposit(&mut self, incognito_address: String, token: AccountId, amount: u128) -> PromiseOrValue<U128> {
-        assert_eq!(env::promise_results_count(), 2, "This is a callback method");
 
-        // handle the result from the second cross contract call this method is a callback for
-        let token_meta_data: FungibleTokenMetadata = match env::promise_result(0) {
-            PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Failed => panic!("{:?}", b"Unable to make comparison"),
-            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<FungibleTokenMetadata>(&result)
-                .unwrap()
-                .into(),
-        };
+    /// restore credit if transfer in claim_credit failed
+    #[private]
+    pub fn resolve_claim_credit(&mut self, token: String, account_id: AccountId, amount: U128) -> bool {
+        if env::promise_results_count() != 1 {
+            BridgeError::CallbackOnly.panic();
+        }
 
-        // handle the result from the first cross contract call this method is a callback for
-        let mut vault_acc_balance: u128 = match env::promise_result(1) {
+        match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Failed => panic!("{:?}", b"Unable to make comparison"),
-            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
-                .unwrap()
-                .into(),
-        };
-
-        let mut emit_amount = amount;
-        if token_meta_data.decimals > 9 {
-            emit_amount = amount.checked_div(u128::pow(10, (token_meta_data.decimals - 9) as u32)).unwrap_or_default();
-            vault_acc_balance = vault_acc_balance.checked_div(u128::pow(10, (token_meta_data.decimals - 9) as u32)).unwrap_or_default();
+            PromiseResult::Successful(_) => {
+                self.release_balance(&token, amount.0);
+                true
+            }
+            PromiseResult::Failed => {
+                let key = (token.clone(), account_id.to_string());
+                let credit = self.credit_amount.get(&key).unwrap_or_default();
+                self.credit_amount.insert(&key, &(credit + amount.0));
+                let total = self.total_credit_amount.get(&token).unwrap_or_default();
+                self.total_credit_amount.insert(&token, &(total + amount.0));
+                self.release_rate_limit(&token, amount.0);
+                BridgeEvent::ClaimCreditFailed {
+                    account: &account_id,
+                    token: &token,
+                    amount,
+                }.emit();
+                false
+            }
         }
+    }
 
-        if vault_acc_balance.cmp(&(u64::MAX as u128)) == Ordering::Greater {
-            panic!("{}", VALUE_EXCEEDED)
+    /// record withdrawal as failed if transfer in withdraw failed
+    #[private]
+    pub fn resolve_withdraw(&mut self, tx_id: [u8; 32], receiver: AccountId, token: String, amount: U128) -> bool {
+        if env::promise_results_count() != 1 {
+            BridgeError::CallbackOnly.panic();
         }
 
-        let decimals_stored = self.token_decimals.get(&token.to_string()).unwrap_or_default();
-        if decimals_stored == 0 {
-            self.token_decimals.insert(&token.to_string(), &token_meta_data.decimals);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                self.release_balance(&token, amount.0);
+                true
+            }
+            PromiseResult::Failed => {
+                self.record_failed_withdrawal(tx_id, receiver, token, amount, None);
+                false
+            }
         }
+    }
+}
 
-        env::log_str(
-            format!(
-                "{} {} {}",
-                incognito_address, token, emit_amount
-            ).as_str());
-
-        PromiseOrValue::Value(U128(0))
+impl Vault {
+    /// keep withdrawal whose transfer failed for retry_withdrawal or claim_failed_withdrawal
+    fn record_failed_withdrawal(
+        &mut self,
+        tx_id: [u8; 32],
+        receiver: AccountId,
+        token: String,
+        amount: U128,
+        msg: Option<String>,
+    ) {
+        BridgeEvent::WithdrawFailed {
+            tx_id: hex::encode(tx_id),
+            receiver: &receiver,
+            token: &token,
+            amount,
+        }.emit();
+        self.failed_withdrawals.insert(&tx_id, &FailedWithdrawal {
+            receiver,
+            token,
+            amount,
+            msg,
+        });
     }
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use crate::test_utils::{call_from, context, new_vault};
+
+    /// vault owned by accounts(0), called by accounts(1)
+    fn setup_vault() -> Vault {
+        let vault = new_vault();
+        call_from(accounts(1));
+        vault
+    }
+
+    fn set_credit(vault: &mut Vault, token: &str, account: &AccountId, amount: u128) {
+        vault.credit_amount.insert(&(token.to_string(), account.to_string()), &amount);
+        vault.total_credit_amount.insert(&token.to_string(), &amount);
+    }
+
+    #[test]
+    fn test_claim_credit() {
+        let mut vault = setup_vault();
+        set_credit(&mut vault, NEAR_ADDRESS, &accounts(1), 100);
+
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(40));
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(1)).0, 60);
+        assert_eq!(vault.get_total_credit(NEAR_ADDRESS.to_string()).0, 60);
+
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(60));
+        assert!(vault.credit_amount.get(&(NEAR_ADDRESS.to_string(), accounts(1).to_string())).is_none());
+    }
+
+    #[test]
+    fn test_claim_burn_credit() {
+        let mut vault = setup_vault();
+        vault.credit_burn(BurnInst {
+            token: NEAR_ADDRESS.to_string(),
+            receiver: accounts(1).to_string(),
+            amount: 100,
+            tx_id: [9u8; 32],
+        });
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(1)).0, 100);
+
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(100));
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(1)).0, 0);
+        assert_eq!(vault.get_total_credit(NEAR_ADDRESS.to_string()).0, 0);
+    }
+
+    #[test]
+    fn test_recover_hex_credit() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        let hex_token = hex::encode(&token);
+        let hex_account: AccountId = hex::encode(accounts(1).as_str()).try_into().unwrap();
+        set_credit(&mut vault, &hex_token, &hex_account, 100);
+
+        assert_eq!(vault.recover_hex_credit(token.clone(), accounts(1)).0, 100);
+        assert_eq!(vault.get_credit(token.clone(), accounts(1)).0, 100);
+        assert_eq!(vault.get_total_credit(token.clone()).0, 100);
+        assert_eq!(vault.get_total_credit(hex_token).0, 0);
+        assert_eq!(vault.recover_hex_credit(token, accounts(1)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Claim amount exceeds credit balance")]
+    fn test_claim_credit_exceeds_balance() {
+        let mut vault = setup_vault();
+        set_credit(&mut vault, NEAR_ADDRESS, &accounts(1), 100);
+
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(101));
+    }
 
     fn to_32_bytes(hex_str: &str) -> [u8; 32] {
         let bytes = hex::decode(hex_str).unwrap();
@@ -365,6 +1049,109 @@ mod tests {
         bytes_
     }
 
+    #[test]
+    #[should_panic(expected = "E47")]
+    fn test_claim_credit_rate_limited() {
+        let mut vault = setup_vault();
+        set_credit(&mut vault, NEAR_ADDRESS, &accounts(1), 100);
+        vault.set_rate_limit(NEAR_ADDRESS.to_string(), Some(RateLimit { max_amount: U128(50), window: U64(1_000) }));
+
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(30));
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(30));
+    }
+
+    #[test]
+    fn test_deposit_receipt() {
+        let mut vault = setup_vault();
+        let storage_cost = Vault::receipt_storage_cost(NEAR_ADDRESS, "incognito_address");
+        testing_env!(context(accounts(1))
+            .attached_deposit(storage_cost + 3 * 1e15 as u128)
+            .block_index(42)
+            .build());
+
+        vault.deposit("incognito_address".to_string());
+        vault.deposit("incognito_address".to_string());
+        assert_eq!(vault.get_deposit_nonce().0, 2);
+        let receipt = vault.get_deposit_receipt(U64(1)).unwrap();
+        assert_eq!(receipt, DepositReceipt {
+            token: NEAR_ADDRESS.to_string(),
+            amount: U128(3),
+            incognito_address: "incognito_address".to_string(),
+            block_height: U64(42),
+        });
+    }
+
+    #[test]
+    fn test_deposit_keeps_whole_units() {
+        let mut vault = setup_vault();
+        let storage_cost = Vault::receipt_storage_cost(NEAR_ADDRESS, "incognito_address");
+        testing_env!(context(accounts(1)).attached_deposit(storage_cost + 3 * 1e15 as u128 + 7).build());
+
+        vault.deposit("incognito_address".to_string());
+        assert_eq!(vault.get_deposit_receipt(U64(0)).unwrap().amount.0, 3);
+        assert_eq!(vault.locked_balances.get(&NEAR_ADDRESS.to_string()), Some(3 * 1e15 as u128));
+    }
+
+    #[test]
+    #[should_panic(expected = "E53")]
+    fn test_deposit_below_unit() {
+        let mut vault = setup_vault();
+        let storage_cost = Vault::receipt_storage_cost(NEAR_ADDRESS, "incognito_address");
+        testing_env!(context(accounts(1)).attached_deposit(storage_cost + 1e15 as u128 - 1).build());
+
+        vault.deposit("incognito_address".to_string());
+    }
+
+    #[test]
+    fn test_claim_failed_withdrawal() {
+        let mut vault = setup_vault();
+        let tx_id = [7u8; 32];
+        vault.failed_withdrawals.insert(&tx_id, &FailedWithdrawal {
+            receiver: accounts(1),
+            token: NEAR_ADDRESS.to_string(),
+            amount: U128(50),
+            msg: None,
+        });
+
+        vault.claim_failed_withdrawal(tx_id);
+        assert!(vault.get_failed_withdrawal(tx_id).is_none());
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(1)).0, 50);
+    }
+
+    #[test]
+    fn test_claim_failed_withdrawal_rate_limited_once() {
+        let mut vault = setup_vault();
+        vault.set_rate_limit(NEAR_ADDRESS.to_string(), Some(RateLimit { max_amount: U128(50), window: U64(1_000) }));
+        // consumed by the withdrawal whose transfer failed
+        assert!(vault.consume_rate_limit(NEAR_ADDRESS, 50));
+        let tx_id = [7u8; 32];
+        vault.failed_withdrawals.insert(&tx_id, &FailedWithdrawal {
+            receiver: accounts(1),
+            token: NEAR_ADDRESS.to_string(),
+            amount: U128(50),
+            msg: None,
+        });
+
+        vault.claim_failed_withdrawal(tx_id);
+        vault.claim_credit(NEAR_ADDRESS.to_string(), U128(50));
+        assert_eq!(vault.get_credit(NEAR_ADDRESS.to_string(), accounts(1)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only withdrawal receiver can claim it")]
+    fn test_claim_failed_withdrawal_not_receiver() {
+        let mut vault = setup_vault();
+        let tx_id = [7u8; 32];
+        vault.failed_withdrawals.insert(&tx_id, &FailedWithdrawal {
+            receiver: accounts(2),
+            token: NEAR_ADDRESS.to_string(),
+            amount: U128(50),
+            msg: None,
+        });
+
+        vault.claim_failed_withdrawal(tx_id);
+    }
+
     #[test]
     fn test_serialize() {
         let msg_obj = InteractRequest {
@@ -385,5 +1172,11 @@ mod tests {
         };
         let msg_str = serde_json::to_string(&msg_obj).unwrap();
         println!("{}", msg_str);
+        assert!(msg_str.contains("\"height\":\"16\""));
+
+        // deprecated numeric height still accepted
+        let numeric = msg_str.replace("\"height\":\"16\"", "\"height\":16");
+        let msg_obj: InteractRequest = serde_json::from_str(&numeric).unwrap();
+        assert_eq!(msg_obj.height, 16);
     }
 }
@@ -0,0 +1,228 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::admin::Role;
+use crate::beacons::decode_legacy_committee;
+use crate::errors::BridgeError;
+use crate::*;
+
+/// version of the `Vault` layout written by this code
+///
+/// 1: owner, roles and pause flags
+/// 2: timelock, withdrawal limits, token registry, storage pool, relayer fees
+///    and committees stored as 64 byte keys
+pub const STATE_VERSION: u32 = 2;
+
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Vault layout deployed before state versioning.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VaultV0 {
+    pub tx_burn: LookupMap<[u8; 32], bool>,
+    // hex encoded keys
+    pub beacons: TreeMap<u128, Vec<String>>,
+    pub total_credit_amount: LookupMap<String, u128>,
+    pub credit_amount: LookupMap<(String, String), u128>,
+    pub token_decimals: LookupMap<String, u8>,
+}
+
+/// Vault layout of state version 1.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VaultV1 {
+    pub tx_burn: LookupMap<[u8; 32], bool>,
+    // hex encoded keys
+    pub beacons: TreeMap<u128, Vec<String>>,
+    pub total_credit_amount: LookupMap<String, u128>,
+    pub credit_amount: LookupMap<(String, String), u128>,
+    pub token_decimals: LookupMap<String, u8>,
+    pub failed_withdrawals: LookupMap<[u8; 32], FailedWithdrawal>,
+    pub deposit_nonce: u64,
+    pub deposit_receipts: LookupMap<u64, DepositReceipt>,
+    pub owner_id: AccountId,
+    pub roles: UnorderedSet<(Role, AccountId)>,
+    pub paused: u8,
+    pub state_version: u32,
+}
+
+/// Any vault layout that may be found in contract state.
+pub enum VersionedVault {
+    V0(Box<VaultV0>),
+    V1(Box<VaultV1>),
+    Current(Box<Vault>),
+}
+
+impl VersionedVault {
+    /// read stored state, trying the latest layout first
+    pub fn read() -> Self {
+        let state = env::storage_read(STATE_KEY).unwrap_or_else(|| BridgeError::StateNotFound.panic());
+        if let Ok(vault) = Vault::try_from_slice(&state) {
+            if vault.state_version == STATE_VERSION {
+                return VersionedVault::Current(Box::new(vault));
+            }
+        }
+        if let Ok(vault) = VaultV1::try_from_slice(&state) {
+            if vault.state_version == 1 {
+                return VersionedVault::V1(Box::new(vault));
+            }
+        }
+        if let Ok(vault) = VaultV0::try_from_slice(&state) {
+            return VersionedVault::V0(Box::new(vault));
+        }
+        BridgeError::UnknownStateVersion.panic()
+    }
+
+    /// upgrade to the latest layout, owner of v0 state defaults to the vault account
+    pub fn into_current(self) -> Vault {
+        match self {
+            VersionedVault::V0(vault) => {
+                let v1 = VaultV1 {
+                    tx_burn: vault.tx_burn,
+                    beacons: vault.beacons,
+                    total_credit_amount: vault.total_credit_amount,
+                    credit_amount: vault.credit_amount,
+                    token_decimals: vault.token_decimals,
+                    failed_withdrawals: LookupMap::new(StorageKey::FailedWithdrawal),
+                    deposit_nonce: 0,
+                    deposit_receipts: LookupMap::new(StorageKey::DepositReceipt),
+                    owner_id: env::current_account_id(),
+                    roles: UnorderedSet::new(StorageKey::Roles),
+                    paused: 0,
+                    state_version: 1,
+                };
+                VersionedVault::V1(Box::new(v1)).into_current()
+            }
+            VersionedVault::V1(vault) => {
+                let mut current = Vault {
+                    tx_burn: vault.tx_burn,
+                    beacons: TreeMap::new(StorageKey::BeaconKeys),
+                    beacon_index: LookupMap::new(StorageKey::BeaconIndex),
+                    total_credit_amount: vault.total_credit_amount,
+                    credit_amount: vault.credit_amount,
+                    token_decimals: vault.token_decimals,
+                    failed_withdrawals: vault.failed_withdrawals,
+                    deposit_nonce: vault.deposit_nonce,
+                    deposit_receipts: vault.deposit_receipts,
+                    owner_id: vault.owner_id,
+                    roles: vault.roles,
+                    paused: vault.paused,
+                    state_version: STATE_VERSION,
+                    upgrade_code_hash: None,
+                    timelock_delay: 0,
+                    proposals: UnorderedMap::new(StorageKey::Proposals),
+                    next_proposal_id: 0,
+                    rate_limits: LookupMap::new(StorageKey::RateLimits),
+                    rate_limit_usage: LookupMap::new(StorageKey::RateLimitUsage),
+                    pending_withdrawals: UnorderedMap::new(StorageKey::PendingWithdrawals),
+                    withdrawal_delays: LookupMap::new(StorageKey::WithdrawalDelays),
+                    tokens: UnorderedMap::new(StorageKey::Tokens),
+                    locked_balances: LookupMap::new(StorageKey::LockedBalances),
+                    total_unshielded: LookupMap::new(StorageKey::TotalUnshielded),
+                    storage_pool: 0,
+                    relayer_fees: LookupMap::new(StorageKey::RelayerFees),
+                    legacy_beacons: vault.beacons,
+                };
+                // only the latest committee is rewritten as bytes to keep migration gas bounded,
+                // older committees stay hex and are decoded by find_committee
+                if let Some(height) = current.legacy_beacons.max() {
+                    let beacons = decode_legacy_committee(&current.legacy_beacons.get(&height).unwrap_or_default());
+                    current.insert_committee(height, &beacons);
+                }
+
+                current
+            }
+            VersionedVault::Current(vault) => *vault,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// migrate stored state to the latest layout after deploying new code
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        VersionedVault::read().into_current()
+    }
+
+    /// get version of the stored state layout
+    pub fn get_state_version(&self) -> u32 {
+        self.state_version
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::call_from;
+
+    #[test]
+    fn test_migrate_v0() {
+        call_from(accounts(0));
+        let mut v0 = VaultV0 {
+            tx_burn: LookupMap::new(StorageKey::Transaction),
+            beacons: TreeMap::new(StorageKey::BeaconHeight),
+            total_credit_amount: LookupMap::new(StorageKey::TokenAccountID),
+            credit_amount: LookupMap::new(StorageKey::TokenUserAccountID),
+            token_decimals: LookupMap::new(StorageKey::TokenDecimals),
+        };
+        v0.tx_burn.insert(&[1u8; 32], &true);
+        v0.beacons.insert(&10, &vec![hex::encode([1u8; 64]), "beacon".to_string()]);
+        v0.beacons.insert(&20, &vec![hex::encode([2u8; 64])]);
+        v0.total_credit_amount.insert(&"token".to_string(), &5);
+        v0.credit_amount.insert(&("token".to_string(), "account".to_string()), &5);
+        v0.token_decimals.insert(&"token".to_string(), &18);
+        env::state_write(&v0);
+
+        let vault = Vault::migrate();
+        assert_eq!(vault.get_state_version(), STATE_VERSION);
+        assert_eq!(vault.get_owner(), accounts(0));
+        assert!(vault.tx_burn.get(&[1u8; 32]).unwrap());
+        // latest committee is rewritten, older ones are read from hex
+        assert_eq!(vault.beacons.len(), 1);
+        assert_eq!(vault.get_beacons(U128Compat(25)), vec![hex::encode([2u8; 64])]);
+        assert_eq!(vault.get_beacons(U128Compat(12)), vec![hex::encode([1u8; 64]), hex::encode([0u8; 64])]);
+        assert_eq!(vault.get_beacon_height(hex::encode([2u8; 64])), Some(U128(20)));
+        assert_eq!(vault.get_total_credit("token".to_string()).0, 5);
+        assert_eq!(vault.credit_amount.get(&("token".to_string(), "account".to_string())), Some(5));
+        assert_eq!(vault.token_decimals.get(&"token".to_string()), Some(18));
+
+        // migrating the latest layout keeps it untouched
+        env::state_write(&vault);
+        let vault = Vault::migrate();
+        assert_eq!(vault.get_total_credit("token".to_string()).0, 5);
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        call_from(accounts(0));
+        let mut v1 = VaultV1 {
+            tx_burn: LookupMap::new(StorageKey::Transaction),
+            beacons: TreeMap::new(StorageKey::BeaconHeight),
+            total_credit_amount: LookupMap::new(StorageKey::TokenAccountID),
+            credit_amount: LookupMap::new(StorageKey::TokenUserAccountID),
+            token_decimals: LookupMap::new(StorageKey::TokenDecimals),
+            failed_withdrawals: LookupMap::new(StorageKey::FailedWithdrawal),
+            deposit_nonce: 7,
+            deposit_receipts: LookupMap::new(StorageKey::DepositReceipt),
+            owner_id: accounts(1),
+            roles: UnorderedSet::new(StorageKey::Roles),
+            paused: 1,
+            state_version: 1,
+        };
+        v1.beacons.insert(&10, &vec![hex::encode([1u8; 64])]);
+        v1.roles.insert(&(Role::Pauser, accounts(2)));
+        env::state_write(&v1);
+
+        let vault = Vault::migrate();
+        assert_eq!(vault.get_state_version(), STATE_VERSION);
+        assert_eq!(vault.get_owner(), accounts(1));
+        assert!(vault.has_role(Role::Pauser, accounts(2)));
+        assert_eq!(vault.deposit_nonce, 7);
+        assert_eq!(vault.paused, 1);
+        assert_eq!(vault.get_beacons(U128Compat(10)), vec![hex::encode([1u8; 64])]);
+        assert_eq!(vault.get_timelock_delay().0, 0);
+    }
+}
@@ -0,0 +1,206 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen};
+
+use crate::*;
+
+/// Max amount released per token within a rolling window.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateLimit {
+    // amount in incognito decimals (9)
+    pub max_amount: U128,
+    // window length in nanoseconds, 0 caps each release at max_amount without tracking usage
+    pub window: U64,
+}
+
+/// Amount released in the current and previous window.
+///
+/// The rolling window is approximated by weighting the previous window
+/// with the part of it still covered by the rolling window.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Default, PartialEq)]
+pub struct RateLimitUsage {
+    pub window_start: u64,
+    pub used: u128,
+    pub prev_used: u128,
+}
+
+impl RateLimitUsage {
+    /// move to the window containing `now`
+    fn roll(&mut self, window: u64, now: u64) {
+        let start = now - now % window;
+        if start != self.window_start {
+            // usage recorded under another window length never rolls over as previous window
+            self.prev_used = if start.checked_sub(self.window_start) == Some(window) { self.used } else { 0 };
+            self.used = 0;
+            self.window_start = start;
+        }
+    }
+
+    /// amount released within the rolling window ending at `now`
+    fn used_at(&self, window: u64, now: u64) -> u128 {
+        let remaining = window.saturating_sub(now.saturating_sub(self.window_start));
+        self.prev_used * remaining as u128 / window as u128 + self.used
+    }
+
+    /// take back amount released in the current window first, then in the previous one
+    fn release(&mut self, amount: u128) {
+        let from_used = amount.min(self.used);
+        self.used -= from_used;
+        self.prev_used = self.prev_used.saturating_sub(amount - from_used);
+    }
+}
+
+impl RateLimit {
+    /// amount that can still be released at `now`
+    pub fn capacity(&self, usage: &RateLimitUsage, now: u64) -> u128 {
+        let window = self.window.0;
+        if window == 0 {
+            return self.max_amount.0;
+        }
+        let mut usage = usage.clone();
+        usage.roll(window, now);
+        self.max_amount.0.saturating_sub(usage.used_at(window, now))
+    }
+}
+
+impl Vault {
+    /// record amount released for token, return false if it exceeds the rate limit
+    pub(crate) fn consume_rate_limit(&mut self, token: &str, amount: u128) -> bool {
+        self.consume_rate_limit_of(token, amount, false)
+    }
+
+    /// same as consume_rate_limit, but a queued withdrawal above max_amount
+    /// is released once nothing else was released in the rolling window
+    pub(crate) fn consume_queued_rate_limit(&mut self, token: &str, amount: u128) -> bool {
+        self.consume_rate_limit_of(token, amount, true)
+    }
+
+    fn consume_rate_limit_of(&mut self, token: &str, amount: u128, allow_oversized: bool) -> bool {
+        let limit = match self.rate_limits.get(&token.to_string()) {
+            Some(limit) => limit,
+            None => return true,
+        };
+        let now = env::block_timestamp();
+        let mut usage = self.rate_limit_usage.get(&token.to_string()).unwrap_or_default();
+        let capacity = limit.capacity(&usage, now);
+        let window_unused = capacity == limit.max_amount.0;
+        if capacity < amount && !(allow_oversized && window_unused) {
+            return false;
+        }
+        if limit.window.0 > 0 {
+            usage.roll(limit.window.0, now);
+            usage.used += amount;
+            self.rate_limit_usage.insert(&token.to_string(), &usage);
+        }
+
+        true
+    }
+
+    /// take back amount consumed for a transfer that didn't release it,
+    /// so moving it to credit doesn't count it twice once claimed
+    pub(crate) fn release_rate_limit(&mut self, token: &str, amount: u128) {
+        let limit = match self.rate_limits.get(&token.to_string()) {
+            Some(limit) if limit.window.0 > 0 => limit,
+            _ => return,
+        };
+        let mut usage = match self.rate_limit_usage.get(&token.to_string()) {
+            Some(usage) => usage,
+            None => return,
+        };
+        usage.roll(limit.window.0, env::block_timestamp());
+        usage.release(amount);
+        self.rate_limit_usage.insert(&token.to_string(), &usage);
+    }
+
+    /// set or remove rate limit of token, executed through timelock
+    ///
+    /// usage restarts as it was recorded for the previous window length
+    pub(crate) fn set_rate_limit(&mut self, token: String, limit: Option<RateLimit>) {
+        self.rate_limit_usage.remove(&token);
+        match limit {
+            Some(limit) => self.rate_limits.insert(&token, &limit),
+            None => self.rate_limits.remove(&token),
+        };
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    // getters
+
+    pub fn get_rate_limit(&self, token: String) -> Option<RateLimit> {
+        self.rate_limits.get(&token)
+    }
+
+    /// get amount of token that can be released now, None if token is not limited
+    pub fn get_rate_limit_capacity(&self, token: String) -> Option<U128> {
+        let limit = self.rate_limits.get(&token)?;
+        let usage = self.rate_limit_usage.get(&token).unwrap_or_default();
+        Some(U128(limit.capacity(&usage, env::block_timestamp())))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_vault;
+
+    const WINDOW: u64 = 100;
+
+    fn limit() -> RateLimit {
+        RateLimit { max_amount: U128(1_000), window: U64(WINDOW) }
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let limit = limit();
+        let mut usage = RateLimitUsage::default();
+        usage.roll(WINDOW, 150);
+        usage.used = 800;
+        assert_eq!(limit.capacity(&usage, 150), 200);
+
+        // half of previous window still in rolling window
+        assert_eq!(limit.capacity(&usage, 250), 600);
+        assert_eq!(limit.capacity(&usage, 299), 992);
+        // previous window fully out of rolling window
+        assert_eq!(limit.capacity(&usage, 300), 1_000);
+    }
+
+    #[test]
+    fn test_window_length_changed() {
+        let mut usage = RateLimitUsage::default();
+        usage.roll(WINDOW, 150);
+        usage.used = 800;
+
+        // longer window starts before the recorded one
+        let longer = RateLimit { max_amount: U128(1_000), window: U64(1_000) };
+        assert_eq!(longer.capacity(&usage, 160), 1_000);
+        // shorter window ending past the recorded one
+        let shorter = RateLimit { max_amount: U128(1_000), window: U64(30) };
+        assert_eq!(shorter.capacity(&usage, 260), 1_000);
+        assert_eq!(usage.used_at(30, 260), 800);
+    }
+
+    #[test]
+    fn test_release() {
+        let mut usage = RateLimitUsage { window_start: 100, used: 100, prev_used: 500 };
+        usage.release(300);
+        assert_eq!((usage.used, usage.prev_used), (0, 300));
+        usage.release(1_000);
+        assert_eq!((usage.used, usage.prev_used), (0, 0));
+    }
+
+    #[test]
+    fn test_zero_window_caps_each_release() {
+        let mut vault = new_vault();
+        let token = "token".to_string();
+        vault.set_rate_limit(token.clone(), Some(RateLimit { max_amount: U128(1_000), window: U64(0) }));
+
+        assert!(vault.consume_rate_limit(&token, 1_000));
+        assert!(vault.consume_rate_limit(&token, 1_000));
+        assert!(!vault.consume_rate_limit(&token, 1_001));
+        assert_eq!(vault.get_rate_limit_capacity(token).unwrap().0, 1_000);
+    }
+}
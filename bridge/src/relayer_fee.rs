@@ -0,0 +1,77 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::U128;
+use near_sdk::{near_bindgen, AccountId};
+
+use crate::*;
+
+const MAX_BPS: u16 = 10_000;
+
+/// Fee carved from a withdrawal for the account submitting its proof.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayerFee {
+    // share of the withdrawal in basis points
+    pub bps: u16,
+    // max fee per withdrawal in incognito decimals (9)
+    pub max_fee: U128,
+}
+
+/// Relayer fee held back with a queued withdrawal, credited when it is finalized.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayerCredit {
+    pub relayer: AccountId,
+    // fee in incognito decimals (9)
+    pub fee: U128,
+}
+
+impl RelayerFee {
+    pub fn is_valid(&self) -> bool {
+        self.bps <= MAX_BPS
+    }
+
+    /// fee for withdrawal of amount in incognito decimals (9)
+    pub fn fee(&self, amount: u128) -> u128 {
+        (amount * self.bps as u128 / MAX_BPS as u128).min(self.max_fee.0)
+    }
+}
+
+impl Vault {
+    /// fee of relayer submitting withdrawal of amount, 0 if token has no fee schedule
+    pub(crate) fn relayer_fee_of(&self, token: &str, amount: u128) -> u128 {
+        self.relayer_fees.get(&token.to_string())
+            .map(|schedule| schedule.fee(amount))
+            .unwrap_or_default()
+    }
+
+    /// set or remove relayer fee of token, executed through timelock
+    pub(crate) fn set_relayer_fee(&mut self, token: String, fee: Option<RelayerFee>) {
+        match fee {
+            Some(fee) => self.relayer_fees.insert(&token, &fee),
+            None => self.relayer_fees.remove(&token),
+        };
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    // getters
+
+    pub fn get_relayer_fee(&self, token: String) -> Option<RelayerFee> {
+        self.relayer_fees.get(&token)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_capped() {
+        let schedule = RelayerFee { bps: 50, max_fee: U128(1_000) };
+        assert_eq!(schedule.fee(100_000), 500);
+        assert_eq!(schedule.fee(1_000_000), 1_000);
+        assert_eq!(schedule.fee(100), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use near_sdk::serde::Serialize;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::near_bindgen;
+
+use crate::token_registry::TokenConfig;
+use crate::utils::NEAR_ADDRESS;
+use crate::*;
+
+/// Backing of a bridged token, amounts are in token decimals unless noted.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenReserves {
+    pub token: String,
+    // registry entry, None for native token and deregistered tokens
+    pub config: Option<TokenConfig>,
+    // amount held by the vault according to its ledger
+    pub locked: U128,
+    // locked in incognito decimals (9), what incognito side can mint against
+    pub locked_normalized: U128,
+    // paid out by withdraw and claim_credit, incognito decimals (9)
+    pub total_unshielded: U128,
+    // credited by submit_burn_proof and not claimed yet, incognito decimals (9)
+    pub total_credit: U128,
+}
+
+#[near_bindgen]
+impl Vault {
+    // getters
+
+    /// get reserves of token, NEAR_ADDRESS for native token
+    pub fn get_reserves(&self, token: String) -> TokenReserves {
+        let locked = self.locked_balances.get(&token).unwrap_or_default();
+        let unit = self.token_unit(&token).unwrap_or(1);
+        TokenReserves {
+            config: self.tokens.get(&token),
+            locked: U128(locked),
+            locked_normalized: U128(locked / unit),
+            total_unshielded: U128(self.total_unshielded.get(&token).unwrap_or_default()),
+            total_credit: U128(self.total_credit_amount.get(&token).unwrap_or_default()),
+            token,
+        }
+    }
+
+    /// list reserves of native token followed by registered tokens
+    pub fn get_supported_tokens(&self, from_index: U64, limit: U64) -> Vec<TokenReserves> {
+        std::iter::once(NEAR_ADDRESS.to_string())
+            .chain(self.tokens.keys())
+            .skip(from_index.0 as usize)
+            .take(limit.0 as usize)
+            .map(|token| self.get_reserves(token))
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{new_vault, register_token, token_config};
+
+    #[test]
+    fn test_supported_tokens() {
+        let mut vault = new_vault();
+        register_token(&mut vault, accounts(3), token_config(12));
+        let token = accounts(3).to_string();
+        vault.lock_balance(&token, 5_000, 1_000);
+        vault.release_balance(&token, 2);
+        vault.total_credit_amount.insert(&token, &1);
+
+        let tokens = vault.get_supported_tokens(U64(0), U64(10));
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token, NEAR_ADDRESS);
+        assert_eq!(tokens[1], TokenReserves {
+            token: token.clone(),
+            config: vault.get_token(accounts(3)),
+            locked: U128(3_000),
+            locked_normalized: U128(3),
+            total_unshielded: U128(2),
+            total_credit: U128(1),
+        });
+        assert_eq!(vault.get_supported_tokens(U64(1), U64(1))[0].token, token);
+    }
+}
@@ -0,0 +1,263 @@
+use std::convert::TryInto;
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds};
+use near_sdk::json_types::U128;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Gas, Promise, PromiseResult};
+
+use crate::admin::Role;
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::*;
+
+// NEP-145 methods of token contracts
+#[ext_contract(ext_storage)]
+pub trait StorageManagement {
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance;
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+}
+
+impl Vault {
+    /// query storage of receiver on the token, then pay in resolve_storage_and_pay
+    pub(crate) fn check_storage_and_pay(
+        &self,
+        tx_id: [u8; 32],
+        account: AccountId,
+        token: String,
+        amount: u128,
+        msg: Option<String>,
+    ) -> Promise {
+        let token_id: AccountId = token.clone().try_into()
+            .unwrap_or_else(|_| BridgeError::InvalidTokenAccount.panic());
+        ext_storage::storage_balance_of(
+            account.clone(),
+            token_id.clone(),
+            0,
+            Gas(5_000_000_000_000),          // gas to attach
+        )
+        .and(ext_storage::storage_balance_bounds(
+            token_id,
+            0,
+            Gas(5_000_000_000_000),          // gas to attach
+        ))
+        .then(ext_self::resolve_storage_and_pay(
+            WithdrawalPayout { tx_id, receiver: account, token, amount: U128(amount), msg },
+            env::current_account_id(),
+            0,
+            Gas(80_000_000_000_000),         // gas to attach to the callback, covers storage_deposit and transfer
+        ))
+    }
+
+    /// amount the token requires to register an account, None if receiver is registered or unknown
+    fn required_storage_deposit() -> Option<u128> {
+        let registered = match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<Option<StorageBalance>>(&result)
+                .map(|balance| balance.is_some())
+                .unwrap_or(true),
+            // token may not implement NEP-145, transfer anyway
+            _ => true,
+        };
+        if registered {
+            return None;
+        }
+        match env::promise_result(1) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<StorageBalanceBounds>(&result)
+                .ok()
+                .map(|bounds| bounds.min.0),
+            _ => None,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// add attached deposit to the pool paying storage of withdrawal receivers, callable by operator
+    #[payable]
+    pub fn top_up_storage_pool(&mut self) -> U128 {
+        self.assert_role(Role::Operator);
+        let amount = env::attached_deposit();
+        self.storage_pool = self.storage_pool.checked_add(amount)
+            .unwrap_or_else(|| BridgeError::AmountOverflow.panic());
+        BridgeEvent::StoragePoolToppedUp {
+            account: &env::predecessor_account_id(),
+            amount: U128(amount),
+            pool: U128(self.storage_pool),
+        }.emit();
+
+        U128(self.storage_pool)
+    }
+
+    // getters
+
+    /// get balance in yocto NEAR of the storage pool
+    pub fn get_storage_pool(&self) -> U128 {
+        U128(self.storage_pool)
+    }
+
+    // fallbacks
+
+    /// register unregistered receiver from the storage pool, then transfer withdrawal
+    ///
+    /// storage fee of the token is deducted from the withdrawal and credited to the owner,
+    /// withdrawal is transferred without registering if the pool can't cover the deposit
+    #[private]
+    pub fn resolve_storage_and_pay(
+        &mut self,
+        payout: WithdrawalPayout,
+    ) -> Promise {
+        if env::promise_results_count() != 2 {
+            BridgeError::CallbackOnly.panic();
+        }
+        let WithdrawalPayout { tx_id, receiver, token, amount, msg } = payout;
+
+        let deposit = match Self::required_storage_deposit() {
+            Some(deposit) if deposit <= self.storage_pool => deposit,
+            _ => return self.transfer_withdrawal(None, tx_id, receiver, token, amount.0, msg),
+        };
+        self.storage_pool -= deposit;
+
+        let fee = self.tokens.get(&token)
+            .and_then(|config| config.storage_fee)
+            .map(|fee| fee.0)
+            .filter(|fee| *fee < amount.0)
+            .unwrap_or_default();
+        if fee > 0 {
+            let owner_id = self.owner_id.clone();
+            self.release_rate_limit(&token, fee);
+            self.add_credit(&token, &owner_id, fee);
+        }
+        BridgeEvent::StorageDepositPaid {
+            receiver: &receiver,
+            token: &token,
+            deposit: U128(deposit),
+            fee: U128(fee),
+        }.emit();
+
+        let token_id: AccountId = token.clone().try_into()
+            .unwrap_or_else(|_| BridgeError::InvalidTokenAccount.panic());
+        let register = ext_storage::storage_deposit(
+            Some(receiver.clone()),
+            Some(true),
+            token_id,
+            deposit,
+            Gas(5_000_000_000_000),          // gas to attach
+        )
+        .then(ext_self::resolve_storage_deposit(
+            U128(deposit),
+            env::current_account_id(),
+            0,
+            Gas(5_000_000_000_000),          // gas to attach to the callback
+        ));
+        self.transfer_withdrawal(Some(register), tx_id, receiver, token, amount.0 - fee, msg)
+    }
+
+    /// return part of deposit the token didn't keep to the pool
+    ///
+    /// a failed storage_deposit returns the whole deposit, registration_only refunds the excess
+    /// over the storage balance, an account registered meanwhile keeps reporting its own balance
+    #[private]
+    pub fn resolve_storage_deposit(&mut self, deposit: U128) {
+        if env::promise_results_count() != 1 {
+            BridgeError::CallbackOnly.panic();
+        }
+
+        let refund = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => deposit.0,
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<StorageBalance>(&result)
+                .map(|balance| deposit.0.saturating_sub(balance.total.0))
+                .unwrap_or_default(),
+        };
+        self.storage_pool = self.storage_pool.saturating_add(refund);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use crate::test_utils::{call_from, callback_results, context, json_result, new_vault, register_token, token_config};
+
+    const DEPOSIT: u128 = 1_250_000_000_000_000_000_000;
+
+    fn storage_results(registered: bool) {
+        let balance = if registered {
+            Some(StorageBalance { total: U128(DEPOSIT), available: U128(0) })
+        } else {
+            None
+        };
+        let bounds = StorageBalanceBounds { min: U128(DEPOSIT), max: Some(U128(DEPOSIT)) };
+        callback_results(vec![json_result(&balance), json_result(&bounds)]);
+    }
+
+    fn payout() -> WithdrawalPayout {
+        WithdrawalPayout {
+            tx_id: [1u8; 32],
+            receiver: accounts(1),
+            token: accounts(3).to_string(),
+            amount: U128(10),
+            msg: None,
+        }
+    }
+
+    fn setup_vault(pool: u128) -> Vault {
+        let mut vault = new_vault();
+        register_token(&mut vault, accounts(3), TokenConfig { storage_fee: Some(U128(2)), ..token_config(9) });
+        testing_env!(context(accounts(0)).attached_deposit(pool).build());
+        vault.top_up_storage_pool();
+        vault
+    }
+
+    #[test]
+    fn test_pool_pays_storage() {
+        let mut vault = setup_vault(DEPOSIT);
+        storage_results(false);
+        vault.resolve_storage_and_pay(payout());
+        assert_eq!(vault.get_storage_pool().0, 0);
+        assert_eq!(vault.get_credit(accounts(3).to_string(), accounts(0)).0, 2);
+    }
+
+    #[test]
+    fn test_registered_receiver() {
+        let mut vault = setup_vault(DEPOSIT);
+        storage_results(true);
+        vault.resolve_storage_and_pay(payout());
+        assert_eq!(vault.get_storage_pool().0, DEPOSIT);
+        assert_eq!(vault.get_credit(accounts(3).to_string(), accounts(0)).0, 0);
+    }
+
+    #[test]
+    fn test_pool_too_small() {
+        let mut vault = setup_vault(DEPOSIT - 1);
+        storage_results(false);
+        vault.resolve_storage_and_pay(payout());
+        assert_eq!(vault.get_storage_pool().0, DEPOSIT - 1);
+    }
+
+    #[test]
+    fn test_failed_storage_deposit_returns_to_pool() {
+        let mut vault = setup_vault(DEPOSIT);
+        storage_results(false);
+        vault.resolve_storage_and_pay(payout());
+
+        callback_results(vec![PromiseResult::Failed]);
+        vault.resolve_storage_deposit(U128(DEPOSIT));
+        assert_eq!(vault.get_storage_pool().0, DEPOSIT);
+    }
+
+    #[test]
+    fn test_storage_deposit_excess_returns_to_pool() {
+        let mut vault = setup_vault(DEPOSIT);
+        callback_results(vec![json_result(&StorageBalance { total: U128(DEPOSIT - 100), available: U128(0) })]);
+        vault.resolve_storage_deposit(U128(DEPOSIT));
+        assert_eq!(vault.get_storage_pool().0, DEPOSIT + 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "E36")]
+    fn test_top_up_without_role() {
+        let mut vault = setup_vault(0);
+        call_from(accounts(1));
+        vault.top_up_storage_pool();
+    }
+}
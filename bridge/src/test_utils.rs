@@ -0,0 +1,66 @@
+use near_sdk::serde::Serialize;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{serde_json, testing_env, AccountId, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+use crate::json_compat::U128Compat;
+use crate::token_registry::TokenConfig;
+use crate::*;
+
+/// hex key of the committee new_vault starts with
+pub fn beacon() -> String {
+    hex::encode([1u8; 64])
+}
+
+/// call to the vault at accounts(0) from account_id
+pub fn context(account_id: AccountId) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder.current_account_id(accounts(0)).predecessor_account_id(account_id);
+    builder
+}
+
+pub fn call_from(account_id: AccountId) {
+    testing_env!(context(account_id).build());
+}
+
+pub fn call_at(account_id: AccountId, timestamp: u64) {
+    testing_env!(context(account_id).block_timestamp(timestamp).build());
+}
+
+/// run a callback with the results of the promises it is attached to
+pub fn callback_results(results: Vec<PromiseResult>) {
+    testing_env!(
+        context(accounts(0)).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        results
+    );
+}
+
+pub fn json_result<T: Serialize>(value: &T) -> PromiseResult {
+    PromiseResult::Successful(serde_json::to_vec(value).unwrap())
+}
+
+/// vault owned by accounts(0) with committee beacon() from height 0
+pub fn new_vault() -> Vault {
+    call_from(accounts(0));
+    Vault::new(vec![beacon()], U128Compat(0))
+}
+
+/// register token through its proposal, executable at once as the timelock delay is 0
+pub fn register_token(vault: &mut Vault, token: AccountId, config: TokenConfig) {
+    let id = vault.register_token(token, config);
+    vault.execute_proposal(id);
+}
+
+/// enabled token without shield limits or storage fee
+pub fn token_config(decimals: u8) -> TokenConfig {
+    TokenConfig {
+        decimals,
+        incognito_token_id: hex::encode([1u8; 32]),
+        enabled: true,
+        min_amount: U128(0),
+        max_amount: None,
+        storage_fee: None,
+    }
+}
@@ -0,0 +1,191 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen, AccountId, PromiseOrValue};
+
+use crate::admin::Role;
+use crate::delayed_withdrawal::WithdrawalDelay;
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::rate_limit::RateLimit;
+use crate::relayer_fee::RelayerFee;
+use crate::token_registry::TokenConfig;
+use crate::*;
+
+/// Admin and governance changes that wait for the timelock delay.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalAction {
+    SetOwner { owner_id: AccountId },
+    GrantRole { role: Role, account_id: AccountId },
+    RevokeRole { role: Role, account_id: AccountId },
+    /// delay in nanoseconds applied to proposals created afterwards
+    SetTimelockDelay { delay: U64 },
+    /// set withdrawal rate limit of token, None removes it
+    SetRateLimit { token: String, limit: Option<RateLimit> },
+    /// set delay for large withdrawals of token, None removes it
+    SetWithdrawalDelay { token: String, config: Option<WithdrawalDelay> },
+    /// set relayer fee of token, None removes it
+    SetRelayerFee { token: String, fee: Option<RelayerFee> },
+    /// deploy code staged by stage_upgrade_code, queued automatically
+    DeployUpgrade { code_hash: String },
+    /// set ledger of token in token decimals, seeds balances bridged before the ledger
+    SetLockedBalance { token: String, amount: U128 },
+    /// register token for shielding, queued by register_token
+    RegisterToken { token: AccountId, config: TokenConfig },
+    /// replace config of registered token, queued by update_token
+    UpdateToken { token: AccountId, config: TokenConfig },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub id: U64,
+    pub action: ProposalAction,
+    pub proposer: AccountId,
+    // block timestamp in nanoseconds the proposal can be executed from
+    pub eta: U64,
+}
+
+impl Vault {
+    /// queue action, executable once the timelock delay passed
+    pub(crate) fn queue_proposal(&mut self, action: ProposalAction) -> u64 {
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        let proposal = Proposal {
+            id: U64(id),
+            action,
+            proposer: env::predecessor_account_id(),
+            eta: U64(env::block_timestamp().saturating_add(self.timelock_delay)),
+        };
+        BridgeEvent::ProposalQueued { proposal: &proposal }.emit();
+        self.proposals.insert(&id, &proposal);
+
+        id
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// propose admin action, callable by owner
+    pub fn propose(&mut self, action: ProposalAction) -> U64 {
+        self.assert_owner();
+        match &action {
+            ProposalAction::DeployUpgrade { .. } => BridgeError::InvalidProposal.panic(),
+            ProposalAction::SetRelayerFee { fee: Some(fee), .. } if !fee.is_valid() => BridgeError::InvalidProposal.panic(),
+            ProposalAction::RegisterToken { config, .. } | ProposalAction::UpdateToken { config, .. } if !config.is_valid() => {
+                BridgeError::InvalidProposal.panic()
+            }
+            _ => {}
+        }
+        U64(self.queue_proposal(action))
+    }
+
+    /// execute proposal after its eta, callable by anyone
+    pub fn execute_proposal(&mut self, id: U64) -> PromiseOrValue<bool> {
+        let proposal = self.proposals.get(&id.0).unwrap_or_else(|| BridgeError::ProposalNotFound.panic());
+        if env::block_timestamp() < proposal.eta.0 {
+            BridgeError::ProposalNotReady.panic();
+        }
+        self.proposals.remove(&id.0);
+        BridgeEvent::ProposalExecuted { id }.emit();
+
+        match proposal.action {
+            ProposalAction::SetOwner { owner_id } => self.set_owner(owner_id),
+            ProposalAction::GrantRole { role, account_id } => self.grant_role(role, account_id),
+            ProposalAction::RevokeRole { role, account_id } => self.revoke_role(role, account_id),
+            ProposalAction::SetTimelockDelay { delay } => self.timelock_delay = delay.0,
+            ProposalAction::SetRateLimit { token, limit } => self.set_rate_limit(token, limit),
+            ProposalAction::SetWithdrawalDelay { token, config } => self.set_withdrawal_delay(token, config),
+            ProposalAction::SetRelayerFee { token, fee } => self.set_relayer_fee(token, fee),
+            ProposalAction::SetLockedBalance { token, amount } => self.set_locked_balance(token, amount),
+            ProposalAction::RegisterToken { token, config } => self.add_token(token, config),
+            ProposalAction::UpdateToken { token, config } => self.replace_token(token, config),
+            ProposalAction::DeployUpgrade { code_hash } => return self.deploy_upgrade(code_hash).into(),
+        }
+
+        PromiseOrValue::Value(true)
+    }
+
+    /// cancel pending proposal, callable by guardian
+    pub fn cancel_proposal(&mut self, id: U64) {
+        self.assert_role(Role::Guardian);
+        let proposal = self.proposals.remove(&id.0).unwrap_or_else(|| BridgeError::ProposalNotFound.panic());
+        if let ProposalAction::DeployUpgrade { .. } = proposal.action {
+            // committee has to authorize again
+            self.cancel_upgrade();
+        }
+        BridgeEvent::ProposalCancelled { id }.emit();
+    }
+
+    // getters
+
+    pub fn get_proposal(&self, id: U64) -> Option<Proposal> {
+        self.proposals.get(&id.0)
+    }
+
+    /// list pending proposals with their eta
+    pub fn get_proposals(&self, from_index: U64, limit: U64) -> Vec<Proposal> {
+        self.proposals
+            .values()
+            .skip(from_index.0 as usize)
+            .take(limit.0 as usize)
+            .collect()
+    }
+
+    /// get delay in nanoseconds between proposing and executing
+    pub fn get_timelock_delay(&self) -> U64 {
+        U64(self.timelock_delay)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{call_at, new_vault};
+
+    const DELAY: u64 = 1_000;
+
+    fn setup_vault() -> Vault {
+        let mut vault = new_vault();
+        vault.timelock_delay = DELAY;
+        vault
+    }
+
+    #[test]
+    fn test_execute_after_delay() {
+        let mut vault = setup_vault();
+        let id = vault.propose(ProposalAction::GrantRole { role: Role::Guardian, account_id: accounts(1) });
+        assert_eq!(vault.get_proposal(id).unwrap().eta, U64(DELAY));
+        assert_eq!(vault.get_proposals(U64(0), U64(10)).len(), 1);
+
+        call_at(accounts(2), DELAY);
+        vault.execute_proposal(id);
+        assert!(vault.has_role(Role::Guardian, accounts(1)));
+        assert!(vault.get_proposals(U64(0), U64(10)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E44")]
+    fn test_execute_before_eta() {
+        let mut vault = setup_vault();
+        let id = vault.propose(ProposalAction::SetOwner { owner_id: accounts(1) });
+        call_at(accounts(0), DELAY - 1);
+        vault.execute_proposal(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "E43")]
+    fn test_cancel_by_guardian() {
+        let mut vault = setup_vault();
+        vault.grant_role(Role::Guardian, accounts(1));
+        let id = vault.propose(ProposalAction::SetOwner { owner_id: accounts(2) });
+
+        call_at(accounts(1), 0);
+        vault.cancel_proposal(id);
+        call_at(accounts(2), DELAY);
+        vault.execute_proposal(id);
+    }
+}
@@ -1,10 +1,12 @@
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{serde_json, env, PromiseOrValue, Gas};
+use near_sdk::{serde_json, env, PromiseOrValue};
 use near_sdk::AccountId;
 use near_sdk::json_types::U128;
 
-use crate::errors::*;
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::utils::is_valid_incognito_address;
 use crate::*;
 
 /// Message parameters to receive via token function call.
@@ -17,48 +19,102 @@ enum TokenReceiverMessage {
     },
 }
 
+/// Why a fungible token deposit was returned to the sender.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum RefundReason {
+    /// token not registered, disabled or amount outside shield limits
+    UnsupportedToken,
+    InvalidIncognitoAddress,
+    /// vault balance would exceed the u64 cap of incognito amounts
+    ValueExceeded,
+    /// amount not above one incognito unit plus the storage fee of the token
+    AmountTooSmall,
+    /// part of amount dropped by scaling to incognito decimals (9)
+    Dust,
+}
+
+impl Vault {
+    /// emit refund event and return amount as unused to the token contract
+    pub(crate) fn refund_deposit(&self, token: &AccountId, amount: u128, reason: RefundReason) -> PromiseOrValue<U128> {
+        BridgeEvent::DepositRefunded {
+            token,
+            amount: U128(amount),
+            reason,
+        }.emit();
+        PromiseOrValue::Value(U128(amount))
+    }
+
+    /// lock amount in ledger and shield it, return dust dropped by scaling as unused
+    ///
+    /// storage fee of the token pays for the receipt and is credited to the owner
+    fn shield_token(
+        &mut self,
+        token: &AccountId,
+        amount: u128,
+        config: &TokenConfig,
+        incognito_address: String,
+    ) -> PromiseOrValue<U128> {
+        let decimals = config.decimals;
+        let unit = if decimals > 9 { u128::pow(10, (decimals - 9) as u32) } else { 1 };
+        let emit_amount = amount / unit;
+        let dust = amount % unit;
+        let fee = config.storage_fee.map(|fee| fee.0).unwrap_or_default();
+        if emit_amount <= fee {
+            return self.refund_deposit(token, amount, RefundReason::AmountTooSmall);
+        }
+        if !self.lock_balance(token.as_str(), amount - dust, unit) {
+            return self.refund_deposit(token, amount, RefundReason::ValueExceeded);
+        }
+
+        if fee > 0 {
+            let owner_id = self.owner_id.clone();
+            self.add_credit(token.as_str(), &owner_id, fee);
+        }
+        self.record_deposit(token.to_string(), emit_amount - fee, incognito_address);
+
+        if dust > 0 {
+            return self.refund_deposit(token, dust, RefundReason::Dust);
+        }
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
 #[near_bindgen]
 impl FungibleTokenReceiver for Vault {
     /// Callback on receiving tokens by this contract.
     /// `msg` format is either "" for deposit or `TokenReceiverMessage`.
-    #[allow(unreachable_code)]
+    /// Unsupported tokens, malformed incognito addresses, deposits over the vault cap
+    /// and dust dropped by scaling to incognito decimals are refunded.
     fn ft_on_transfer(
         &mut self,
         sender_id: AccountId,
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
+        self.assert_not_paused(Feature::FtDeposit);
         let token_in = env::predecessor_account_id();
+        let token_config = match self.tokens.get(&token_in.to_string()) {
+            Some(config) if config.accepts(amount.0) => config,
+            _ => return self.refund_deposit(&token_in, amount.0, RefundReason::UnsupportedToken),
+        };
         if msg.is_empty() {
-            panic!("{}", INVALID_MESSAGE)
+            BridgeError::InvalidMessage.panic()
         }
         // shield request
         let message =
-            serde_json::from_str::<TokenReceiverMessage>(&msg).expect(ERR28_WRONG_MSG_FORMAT);
+            serde_json::from_str::<TokenReceiverMessage>(&msg)
+                .unwrap_or_else(|_| BridgeError::WrongMsgFormat.panic());
         match message {
             TokenReceiverMessage::Deposit {
                 incognito_address
             } => {
                 let amount = amount.0;
-                ext_ft::ft_metadata(
-                    token_in.clone(),
-                    0,
-                    Gas(5_000_000_000_000),          // gas to attach
-                )
-                .and(ext_ft::ft_balance_of(
-                    env::current_account_id().clone(),
-                    token_in.clone(),
-                    0,
-                    Gas(5_000_000_000_000),         // gas to attach
-                ))
-                .then(ext_self::fallback_deposit(
-                    incognito_address,
-                    token_in,
-                    amount,
-                    env::current_account_id().clone(),
-                    0,
-                    Gas(5_000_000_000_000),          // gas to attach to the callback
-                )).into()
+                if !is_valid_incognito_address(&incognito_address) {
+                    return self.refund_deposit(&token_in, amount, RefundReason::InvalidIncognitoAddress);
+                }
+                self.shield_token(&token_in, amount, &token_config, incognito_address)
             }
         }
     }
@@ -69,6 +125,8 @@ impl FungibleTokenReceiver for Vault {
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{call_from, new_vault, register_token, token_config};
 
     #[test]
     fn test_serialize() {
@@ -79,6 +137,68 @@ mod tests {
         println!("{}", msg_str);
     }
 
+    const ADDRESS: &str = "12sxXUjkMJZHz6diDB6yYnSjyYcDYiT5QygUYFsUbGUqK8PH8uhxf4LePiAE8UYoDcNkHAdJJtT1J6T8hcvpZoWLHAp8g6h1BQEfp4h5LQgEPuhMpnVMquvr1xXZZueLhTNCXc8fkVXseeVAGCt8";
+
+    fn setup_vault(decimals: u8) -> Vault {
+        let mut vault = new_vault();
+        register_token(&mut vault, accounts(3), token_config(decimals));
+        vault
+    }
+
+    /// transfer amount of token from accounts(3), return refunded amount
+    fn transfer(vault: &mut Vault, token: AccountId, amount: u128, address: &str) -> u128 {
+        call_from(token);
+        let msg = format!(r#"{{"incognito_address":"{}"}}"#, address);
+        match vault.ft_on_transfer(accounts(1), U128(amount), msg) {
+            PromiseOrValue::Value(refund) => refund.0,
+            PromiseOrValue::Promise(_) => panic!("expected value"),
+        }
+    }
+
+    #[test]
+    fn test_refund_unregistered_token() {
+        let mut vault = setup_vault(18);
+        assert_eq!(transfer(&mut vault, accounts(4), 100, ADDRESS), 100);
+        assert_eq!(vault.get_deposit_nonce().0, 0);
+    }
+
+    #[test]
+    fn test_refund_invalid_address() {
+        let mut vault = setup_vault(18);
+        assert_eq!(transfer(&mut vault, accounts(3), 100, "not base58 0OIl"), 100);
+        assert_eq!(vault.get_deposit_nonce().0, 0);
+    }
+
+    #[test]
+    fn test_refund_dust() {
+        let mut vault = setup_vault(12);
+        assert_eq!(transfer(&mut vault, accounts(3), 5_000_000_123, ADDRESS), 123);
+        assert_eq!(vault.get_deposit_receipt(U64(0)).unwrap().amount.0, 5_000_000);
+        assert_eq!(vault.get_locked_balance(accounts(3).to_string()).0, 5_000_000_000);
+    }
+
+    #[test]
+    fn test_storage_fee_deducted() {
+        let mut vault = new_vault();
+        let mut config = token_config(9);
+        config.storage_fee = Some(U128(10));
+        register_token(&mut vault, accounts(3), config);
+
+        assert_eq!(transfer(&mut vault, accounts(3), 10, ADDRESS), 10);
+        assert_eq!(transfer(&mut vault, accounts(3), 100, ADDRESS), 0);
+        assert_eq!(vault.get_deposit_receipt(U64(0)).unwrap().amount.0, 90);
+        assert_eq!(vault.get_credit(accounts(3).to_string(), accounts(0)).0, 10);
+        assert_eq!(vault.get_locked_balance(accounts(3).to_string()).0, 100);
+    }
+
+    #[test]
+    fn test_refund_over_cap() {
+        let mut vault = setup_vault(9);
+        assert_eq!(transfer(&mut vault, accounts(3), u64::MAX as u128, ADDRESS), 0);
+        assert_eq!(transfer(&mut vault, accounts(3), 1, ADDRESS), 1);
+        assert_eq!(vault.get_deposit_nonce().0, 1);
+    }
+
     #[test]
     fn test_deserialize() {
         let msg_str = r#"{"incognito_address":"my_address"}"#;
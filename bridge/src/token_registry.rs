@@ -0,0 +1,204 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{near_bindgen, AccountId};
+
+use crate::admin::Role;
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::timelock::ProposalAction;
+use crate::*;
+
+/// max decimals of a token, incognito amounts up to u64 scaled by 10^(decimals - 9) fit in u128
+const MAX_DECIMALS: u8 = 24;
+
+/// Fungible token accepted for shielding.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenConfig {
+    // decimals of the token contract, cached so deposits skip ft_metadata
+    pub decimals: u8,
+    // hex encoded incognito token id the token is minted as
+    pub incognito_token_id: String,
+    // disabled tokens are refunded on deposit, withdrawals keep working
+    pub enabled: bool,
+    // min amount per shield in token decimals
+    pub min_amount: U128,
+    // max amount per shield in token decimals, None for no limit
+    pub max_amount: Option<U128>,
+    // fee in incognito decimals (9) covering NEAR storage the vault pays for the token,
+    // deducted from each deposit for its receipt and from withdrawal when the vault
+    // registers the receiver on the token, None to pay storage from the vault balance
+    pub storage_fee: Option<U128>,
+}
+
+impl TokenConfig {
+    pub fn is_valid(&self) -> bool {
+        let id_valid = hex::decode(&self.incognito_token_id).map(|id| id.len() == 32).unwrap_or(false);
+        let range_valid = self.max_amount.map(|max| max.0 >= self.min_amount.0).unwrap_or(true);
+        id_valid && range_valid && self.decimals <= MAX_DECIMALS
+    }
+
+    fn assert_valid(&self) {
+        if !self.is_valid() {
+            BridgeError::InvalidTokenConfig.panic();
+        }
+    }
+
+    /// check token is enabled and amount is within shield limits
+    pub fn accepts(&self, amount: u128) -> bool {
+        self.enabled
+            && amount >= self.min_amount.0
+            && self.max_amount.map(|max| amount <= max.0).unwrap_or(true)
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// propose registering token for shielding, callable by token manager
+    ///
+    /// registration is queued behind the timelock, return the proposal id,
+    /// decimals of a token registered before can not change
+    pub fn register_token(&mut self, token: AccountId, config: TokenConfig) -> U64 {
+        self.assert_role(Role::TokenManager);
+        self.assert_token_registered(&token, false);
+        config.assert_valid();
+        U64(self.queue_proposal(ProposalAction::RegisterToken { token, config }))
+    }
+
+    /// propose updating config of registered token, callable by token manager
+    ///
+    /// update is queued behind the timelock, return the proposal id
+    pub fn update_token(&mut self, token: AccountId, config: TokenConfig) -> U64 {
+        self.assert_role(Role::TokenManager);
+        self.assert_token_registered(&token, true);
+        config.assert_valid();
+        U64(self.queue_proposal(ProposalAction::UpdateToken { token, config }))
+    }
+
+    /// remove token from registry, callable by token manager
+    ///
+    /// decimals stay cached so pending withdrawals and credits can be paid
+    pub fn deregister_token(&mut self, token: AccountId) {
+        self.assert_role(Role::TokenManager);
+        if self.tokens.remove(&token.to_string()).is_none() {
+            BridgeError::TokenNotRegistered.panic();
+        }
+        BridgeEvent::TokenDeregistered { token: &token }.emit();
+    }
+
+    // getters
+
+    pub fn get_token(&self, token: AccountId) -> Option<TokenConfig> {
+        self.tokens.get(&token.to_string())
+    }
+
+    pub fn get_token_count(&self) -> U64 {
+        U64(self.tokens.len())
+    }
+}
+
+impl Vault {
+    fn assert_token_registered(&self, token: &AccountId, registered: bool) {
+        match self.tokens.get(&token.to_string()) {
+            Some(_) if !registered => BridgeError::TokenAlreadyRegistered.panic(),
+            None if registered => BridgeError::TokenNotRegistered.panic(),
+            _ => {}
+        }
+    }
+
+    /// register token, executed through timelock
+    pub(crate) fn add_token(&mut self, token: AccountId, config: TokenConfig) {
+        self.assert_token_registered(&token, false);
+        self.write_token(token, config);
+    }
+
+    /// replace config of registered token, executed through timelock
+    pub(crate) fn replace_token(&mut self, token: AccountId, config: TokenConfig) {
+        self.assert_token_registered(&token, true);
+        self.write_token(token, config);
+    }
+
+    fn write_token(&mut self, token: AccountId, config: TokenConfig) {
+        config.assert_valid();
+        let key = token.to_string();
+        match self.token_decimals.get(&key) {
+            Some(decimals) if decimals != config.decimals => BridgeError::InvalidTokenConfig.panic(),
+            Some(_) => {}
+            None => {
+                self.token_decimals.insert(&key, &config.decimals);
+            }
+        }
+        BridgeEvent::TokenRegistered { token: &token, config: &config }.emit();
+        self.tokens.insert(&key, &config);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{call_at, call_from, new_vault, register_token, token_config};
+
+    fn config(decimals: u8) -> TokenConfig {
+        TokenConfig { min_amount: U128(10), max_amount: Some(U128(1_000)), ..token_config(decimals) }
+    }
+
+    #[test]
+    fn test_register_token() {
+        let mut vault = new_vault();
+        vault.grant_role(Role::TokenManager, accounts(1));
+        call_from(accounts(1));
+        let id = vault.register_token(accounts(3), config(18));
+        assert!(vault.get_token(accounts(3)).is_none());
+        vault.execute_proposal(id);
+        assert_eq!(vault.token_decimals.get(&accounts(3).to_string()), Some(18));
+
+        let token = vault.get_token(accounts(3)).unwrap();
+        assert!(token.accepts(10));
+        assert!(!token.accepts(9));
+        assert!(!token.accepts(1_001));
+
+        vault.deregister_token(accounts(3));
+        assert!(vault.get_token(accounts(3)).is_none());
+        // cached decimals kept for withdrawals
+        assert_eq!(vault.token_decimals.get(&accounts(3).to_string()), Some(18));
+    }
+
+    #[test]
+    #[should_panic(expected = "E36")]
+    fn test_register_without_role() {
+        let mut vault = new_vault();
+        call_from(accounts(1));
+        vault.register_token(accounts(3), config(18));
+    }
+
+    #[test]
+    #[should_panic(expected = "E52")]
+    fn test_register_changed_decimals() {
+        let mut vault = new_vault();
+        register_token(&mut vault, accounts(3), config(18));
+        vault.deregister_token(accounts(3));
+        register_token(&mut vault, accounts(3), config(6));
+    }
+
+    #[test]
+    #[should_panic(expected = "E52")]
+    fn test_register_too_many_decimals() {
+        let mut vault = new_vault();
+        vault.register_token(accounts(3), config(MAX_DECIMALS + 1));
+    }
+
+    #[test]
+    fn test_update_after_timelock() {
+        let mut vault = new_vault();
+        register_token(&mut vault, accounts(3), config(18));
+        vault.timelock_delay = 1_000;
+        let id = vault.update_token(accounts(3), TokenConfig { storage_fee: Some(U128(5)), ..config(18) });
+        assert_eq!(vault.get_token(accounts(3)).unwrap().storage_fee, None);
+
+        call_at(accounts(2), 1_000);
+        vault.execute_proposal(id);
+        assert_eq!(vault.get_token(accounts(3)).unwrap().storage_fee, Some(U128(5)));
+    }
+}
@@ -0,0 +1,196 @@
+use std::convert::TryFrom;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near_bindgen, AccountId, Gas, Promise};
+
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::instructions::{decode_hex, UpgradeInst};
+use crate::timelock::ProposalAction;
+use crate::utils::verify_inst;
+use crate::*;
+
+const STAGED_CODE_KEY: &[u8] = b"STAGED_CODE";
+// account that staged the code and its storage deposit in yocto NEAR
+const STAGED_DEPOSIT_KEY: &[u8] = b"STAGED_DEPOSIT";
+const GAS_FOR_MIGRATE: Gas = Gas(100_000_000_000_000);
+
+impl Vault {
+    /// deploy staged code and migrate state, executed through timelock
+    pub(crate) fn deploy_upgrade(&mut self, code_hash: String) -> Promise {
+        if self.upgrade_code_hash.map(hex::encode) != Some(code_hash.clone()) {
+            BridgeError::CodeHashMismatch.panic();
+        }
+        let code = env::storage_read(STAGED_CODE_KEY).unwrap_or_else(|| BridgeError::NoStagedCode.panic());
+        Self::remove_staged_code();
+        self.upgrade_code_hash = None;
+        BridgeEvent::UpgradeDeployed { code_hash }.emit();
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), Vec::new(), 0, GAS_FOR_MIGRATE)
+    }
+
+    /// drop authorized upgrade and its staged code
+    pub(crate) fn cancel_upgrade(&mut self) {
+        Self::remove_staged_code();
+        self.upgrade_code_hash = None;
+    }
+
+    /// remove staged code and refund its storage deposit to the account that staged it
+    fn remove_staged_code() {
+        env::storage_remove(STAGED_CODE_KEY);
+        let staged = env::storage_read(STAGED_DEPOSIT_KEY)
+            .and_then(|value| <(AccountId, u128)>::try_from_slice(&value).ok());
+        env::storage_remove(STAGED_DEPOSIT_KEY);
+        if let Some((account_id, deposit)) = staged.filter(|(_, deposit)| *deposit > 0) {
+            Promise::new(account_id).transfer(deposit);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// authorize upgrade
+    ///
+    /// verify beacon committee signed the sha256 hash of the new contract code
+    pub fn authorize_upgrade(
+        &mut self,
+        upgrade_info: InteractRequest
+    ) {
+        let beacons = self.committee(upgrade_info.height);
+
+        // verify instruction
+        verify_inst(&upgrade_info, beacons);
+
+        // parse instruction
+        let inst = decode_hex(&upgrade_info.inst)
+            .and_then(|inst| UpgradeInst::try_from(inst.as_slice()))
+            .unwrap_or_else(|e| BridgeError::from(e).panic());
+        let UpgradeInst { code_hash, tx_id } = inst;
+
+        // check instruction used, shared with burn transactions
+        if self.tx_burn.get(&tx_id).unwrap_or_default() {
+            BridgeError::InvalidTxBurn.panic();
+        }
+        self.tx_burn.insert(&tx_id, &true);
+
+        // code staged for a previous authorization no longer applies
+        Self::remove_staged_code();
+        self.upgrade_code_hash = Some(code_hash);
+        BridgeEvent::UpgradeAuthorized { code_hash: hex::encode(code_hash) }.emit();
+    }
+
+    /// stage upgrade code
+    ///
+    /// input is the raw wasm, its sha256 hash must match the authorized one
+    /// deployment is queued behind the timelock, return the proposal id
+    ///
+    /// attached deposit must cover storage of the code, it is held until the code
+    /// is deployed or dropped and the excess is refunded now
+    #[payable]
+    pub fn stage_upgrade_code(&mut self) -> U64 {
+        let code_hash = self.upgrade_code_hash.unwrap_or_else(|| BridgeError::UpgradeNotAuthorized.panic());
+        if env::storage_has_key(STAGED_CODE_KEY) {
+            BridgeError::UpgradeAlreadyStaged.panic();
+        }
+        let code = env::input().unwrap_or_default();
+        if env::sha256(&code) != code_hash {
+            BridgeError::CodeHashMismatch.panic();
+        }
+        let initial_usage = env::storage_usage();
+        let stager = env::predecessor_account_id();
+        // deposit takes the same bytes whatever its amount, written once to measure
+        let record = |deposit: u128| (stager.clone(), deposit).try_to_vec().unwrap_or_default();
+        env::storage_write(STAGED_CODE_KEY, &code);
+        env::storage_write(STAGED_DEPOSIT_KEY, &record(0));
+        let deposit = (env::storage_usage() - initial_usage) as u128 * env::storage_byte_cost();
+        let attached = env::attached_deposit();
+        if attached < deposit {
+            BridgeError::InsufficientStorageDeposit.panic();
+        }
+        env::storage_write(STAGED_DEPOSIT_KEY, &record(deposit));
+        if attached > deposit {
+            Promise::new(stager.clone()).transfer(attached - deposit);
+        }
+        BridgeEvent::UpgradeStaged { code_hash: hex::encode(code_hash) }.emit();
+
+        U64(self.queue_proposal(ProposalAction::DeployUpgrade { code_hash: hex::encode(code_hash) }))
+    }
+
+    /// get hex sha256 hash of the code authorized for upgrade
+    pub fn get_upgrade_code_hash(&self) -> Option<String> {
+        self.upgrade_code_hash.map(hex::encode)
+    }
+
+    /// check code for the authorized upgrade is staged
+    pub fn is_upgrade_staged(&self) -> bool {
+        env::storage_has_key(STAGED_CODE_KEY)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use crate::test_utils::{context, new_vault};
+
+    const CODE: &[u8] = b"\0asm new code";
+    // covers storage of CODE and the deposit record
+    const STORAGE_DEPOSIT: u128 = 10u128.pow(22);
+
+    fn setup_vault(input: &[u8]) -> Vault {
+        let mut vault = new_vault();
+        let mut code_hash = [0u8; 32];
+        code_hash.copy_from_slice(&env::sha256(CODE));
+        vault.upgrade_code_hash = Some(code_hash);
+
+        // raw wasm is passed as method input
+        let mut ctx = context(accounts(0)).attached_deposit(STORAGE_DEPOSIT).build();
+        ctx.input = input.to_vec();
+        testing_env!(ctx);
+        vault
+    }
+
+    #[test]
+    fn test_stage_and_deploy() {
+        let mut vault = setup_vault(CODE);
+        let id = vault.stage_upgrade_code();
+        assert!(vault.is_upgrade_staged());
+        assert!(env::storage_has_key(STAGED_DEPOSIT_KEY));
+
+        vault.execute_proposal(id);
+        assert!(!vault.is_upgrade_staged());
+        assert!(!env::storage_has_key(STAGED_DEPOSIT_KEY));
+        assert_eq!(vault.get_upgrade_code_hash(), None);
+    }
+
+    #[test]
+    fn test_cancel_staged_upgrade() {
+        let mut vault = setup_vault(CODE);
+        let id = vault.stage_upgrade_code();
+
+        vault.cancel_proposal(id);
+        assert!(!vault.is_upgrade_staged());
+        assert!(!env::storage_has_key(STAGED_DEPOSIT_KEY));
+        assert_eq!(vault.get_upgrade_code_hash(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E55")]
+    fn test_stage_without_deposit() {
+        let mut vault = setup_vault(CODE);
+        let mut ctx = context(accounts(0)).build();
+        ctx.input = CODE.to_vec();
+        testing_env!(ctx);
+        vault.stage_upgrade_code();
+    }
+
+    #[test]
+    #[should_panic(expected = "E41")]
+    fn test_stage_wrong_code() {
+        let mut vault = setup_vault(b"\0asm other code");
+        vault.stage_upgrade_code();
+    }
+}
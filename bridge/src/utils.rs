@@ -1,63 +1,116 @@
-use crate::{errors::*, InteractRequest};
+use std::convert::TryFrom;
+use crate::{errors::BridgeError, InteractRequest};
+use crate::beacons::BeaconKey;
+use crate::borsh_request::BorshInteractRequest;
 use near_sdk::{env};
 
 pub const WITHDRAW_METADATA: u8 = 157;
 pub const SWAP_BEACON_METADATA: u8 = 158;
 pub const BURN_METADATA: u8 = 160;
+pub const UPGRADE_METADATA: u8 = 161;
+pub const WITHDRAW_CALL_METADATA: u8 = 162;
 
+// bytes staked per storage record on top of its key and value
+pub const STORAGE_RECORD_OVERHEAD: usize = 40;
 pub const NEAR_ADDRESS: &str = "0000000000000000000000000000000000000001";
 pub const WITHDRAW_INST_LEN: usize = 1 + 1 + 1 + 64 + 1 + 64 + 32 + 32; // ignore last 64 bytes in instruction
 pub const SWAP_COMMITTEE_INST_LEN: usize = 1 + 1 + 32 + 32 + 32;
+pub const UPGRADE_INST_LEN: usize = 1 + 1 + 32 + 32;
+pub const INCOGNITO_ADDRESS_MAX_LEN: usize = 256;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// check incognito payment address is a non empty base58 string of bounded length
+pub fn is_valid_incognito_address(address: &str) -> bool {
+    !address.is_empty()
+        && address.len() <= INCOGNITO_ADDRESS_MAX_LEN
+        && address.bytes().all(|c| BASE58_ALPHABET.contains(&c))
+}
 
 pub fn verify_inst(
-    request_info: &InteractRequest, beacons: Vec<String>,
+    request_info: &InteractRequest, beacons: Vec<BeaconKey>,
+) {
+    let proof = BorshInteractRequest::try_from(request_info).unwrap_or_else(|e| e.panic());
+    verify_proof(&proof, beacons);
+}
+
+pub fn verify_proof(
+    request_info: &BorshInteractRequest, beacons: Vec<BeaconKey>,
 ) {
+    let blk = block_hash(request_info);
+    check_proof(request_info, &beacons, &blk).unwrap_or_else(|e| e.panic());
+}
+
+/// double keccak of blk_data and inst_root, signed by the beacon committee
+pub fn block_hash(request_info: &BorshInteractRequest) -> [u8; 32] {
+    let mut blk_data_bytes = request_info.blk_data.to_vec();
+    blk_data_bytes.extend_from_slice(&request_info.inst_root);
+    env::keccak256_array(env::keccak256(blk_data_bytes.as_slice()).as_slice())
+}
+
+/// verify committee signatures on block hash `blk` and instruction inclusion in inst_root
+pub fn check_proof(
+    request_info: &BorshInteractRequest, beacons: &[BeaconKey], blk: &[u8; 32],
+) -> Result<(), BridgeError> {
     if request_info.indexes.len() != request_info.signatures.len()
         || request_info.signatures.len() != request_info.vs.len()
     {
-        panic!("{}", INVALID_KEY_AND_INDEX);
+        return Err(BridgeError::InvalidKeyAndIndex);
     }
 
-    if beacons.len().eq(&0) {
-        panic!("{}", INVALID_BEACON_LIST);
+    if beacons.is_empty() {
+        return Err(BridgeError::InvalidBeaconList);
     }
-    if request_info.signatures.len() <= beacons.len() * 2 / 3 {
-        panic!("{}", INVALID_NUMBER_OF_SIGS);
+    let num_signers = check_signers(&request_info.indexes, beacons.len())?;
+    if num_signers <= beacons.len() * 2 / 3 {
+        return Err(BridgeError::InvalidNumberOfSigs);
     }
 
-    let mut blk_data_bytes = request_info.blk_data.to_vec();
-        blk_data_bytes.extend_from_slice(&request_info.inst_root);
-        // Get double block hash from instRoot and other data
-        let blk = env::keccak256_array(env::keccak256(blk_data_bytes.as_slice()).as_slice());
-
-        // verify beacon signature
-        for i in 0..request_info.indexes.len() {
-            let (s_r, v) = (hex::decode(request_info.signatures[i].clone()).unwrap_or_default(), request_info.vs[i]);
-            let index_beacon = request_info.indexes[i];
-            let beacon_key = beacons[index_beacon as usize].clone();
-            let recover_key = env::ecrecover(
-                &blk,
-                s_r.as_slice(),
-                v,
-                false,
-            ).unwrap();
-            if !hex::encode(recover_key).eq(beacon_key.as_str()) {
-                panic!("{}", INVALID_BEACON_SIGNATURE);
-            }
+    // verify beacon signature
+    for i in 0..request_info.indexes.len() {
+        let s_r = &request_info.signatures[i];
+        let v = request_info.vs[i];
+        let index_beacon = request_info.indexes[i];
+        let beacon_key = &beacons[index_beacon as usize];
+        let recover_key = env::ecrecover(
+            blk,
+            s_r,
+            v,
+            false,
+        ).ok_or(BridgeError::InvalidBeaconSignature)?;
+        if recover_key != *beacon_key {
+            return Err(BridgeError::InvalidBeaconSignature);
         }
-        // append block height to instruction
-        let height_vec = append_at_top(request_info.height);
-        let mut inst_vec = hex::decode(&request_info.inst).unwrap_or_default();
-        inst_vec.extend_from_slice(&height_vec);
-        let inst_hash = env::keccak256_array(inst_vec.as_slice());
-        if !instruction_in_merkle_tree(
-            &inst_hash,
-            &request_info.inst_root,
-            &request_info.inst_paths,
-            &request_info.inst_path_is_lefts
-        ) {
-            panic!("{}", INVALID_MERKLE_TREE);
+    }
+    // append block height to instruction
+    let height_vec = append_at_top(request_info.height);
+    let mut inst_vec = request_info.inst.clone();
+    inst_vec.extend_from_slice(&height_vec);
+    let inst_hash = env::keccak256_array(inst_vec.as_slice());
+    if !instruction_in_merkle_tree(&inst_hash, request_info) {
+        return Err(BridgeError::InvalidMerkleTree);
+    }
+
+    Ok(())
+}
+
+/// validate signer indexes against committee size, return number of distinct signers
+///
+/// every index must be in range and used once, so one signature can't count twice towards quorum
+pub fn check_signers(indexes: &[u8], num_beacons: usize) -> Result<usize, BridgeError> {
+    let mut signed = vec![false; num_beacons];
+    for index in indexes.iter() {
+        let index = *index as usize;
+        if index >= num_beacons {
+            return Err(BridgeError::SignerIndexOutOfRange);
         }
+        if signed[index] {
+            return Err(BridgeError::DuplicateSignerIndex);
+        }
+        signed[index] = true;
+    }
+
+    Ok(indexes.len())
 }
 
 fn append_at_top(input: u128) -> Vec<u8>  {
@@ -71,17 +124,16 @@ fn append_at_top(input: u128) -> Vec<u8>  {
 
 fn instruction_in_merkle_tree(
     leaf: &[u8; 32],
-    root: &[u8; 32],
-    paths: &Vec<[u8; 32]>,
-    path_lefts: &Vec<bool>
+    request_info: &BorshInteractRequest,
 ) -> bool {
-    if paths.len() != path_lefts.len() {
+    if !request_info.has_valid_path_bitmap() {
         return false;
     }
+    let paths = &request_info.inst_paths;
     let mut build_root = leaf.clone();
     let mut temp;
     for i in 0..paths.len() {
-        if path_lefts[i] {
+        if request_info.is_left(i) {
             temp = paths[i][..].to_vec();
             temp.extend_from_slice(&build_root[..]);
         } else if paths[i] == [0; 32] {
@@ -93,5 +145,61 @@ fn instruction_in_merkle_tree(
         }
         build_root = env::keccak256_array(&temp[..]);
     }
-    build_root == *root
-}
\ No newline at end of file
+    build_root == request_info.inst_root
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &str = "3ba689cfbcbfe81d10f47c0becd911ece7fd1c99ce3bf84c61cf20f3bfc2979438251b39a913e934bd6b61def19fac8da98808cce9b8f428809885364a49d81c";
+
+    fn forged_request(indexes: Vec<u8>) -> InteractRequest {
+        InteractRequest {
+            inst: "9d01".to_string(),
+            height: 1,
+            inst_paths: vec![],
+            inst_path_is_lefts: vec![],
+            inst_root: [0u8; 32],
+            blk_data: [0u8; 32],
+            signatures: vec![SIGNATURE.to_string(); indexes.len()],
+            vs: vec![0; indexes.len()],
+            indexes,
+        }
+    }
+
+    fn beacons(num: usize) -> Vec<BeaconKey> {
+        (0..num).map(|i| {
+            let mut key = [0u8; 64];
+            key[63] = i as u8;
+            key
+        }).collect()
+    }
+
+    #[test]
+    fn test_check_signers() {
+        assert_eq!(check_signers(&[0, 1, 3], 4), Ok(3));
+        assert_eq!(check_signers(&[3, 0, 1], 4), Ok(3));
+        assert_eq!(check_signers(&[0, 1, 1], 4), Err(BridgeError::DuplicateSignerIndex));
+        assert_eq!(check_signers(&[0, 4], 4), Err(BridgeError::SignerIndexOutOfRange));
+    }
+
+    #[test]
+    #[should_panic(expected = "E33")]
+    fn test_reject_repeated_signature() {
+        // one beacon signature repeated to fake quorum
+        verify_inst(&forged_request(vec![2, 2, 2]), beacons(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "E34")]
+    fn test_reject_out_of_range_index() {
+        verify_inst(&forged_request(vec![0, 1, 200]), beacons(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "E03")]
+    fn test_reject_below_quorum() {
+        verify_inst(&forged_request(vec![0, 1]), beacons(4));
+    }
+}
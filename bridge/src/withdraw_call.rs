@@ -0,0 +1,160 @@
+use std::convert::TryInto;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Gas, Promise, PromiseResult};
+
+use crate::errors::BridgeError;
+use crate::events::BridgeEvent;
+use crate::*;
+
+impl Vault {
+    /// ft_transfer_call of withdrawal and its callback, refunds are credited in resolve_withdraw_call
+    pub(crate) fn withdraw_call_promises(
+        &self,
+        tx_id: [u8; 32],
+        account: AccountId,
+        token: String,
+        amount: u128,
+        msg: String,
+    ) -> (Promise, Promise) {
+        let token_amount = self.to_token_amount(&token, amount);
+        let token_id: AccountId = token.clone().try_into()
+            .unwrap_or_else(|_| BridgeError::InvalidTokenAccount.panic());
+        let transfer = ext_ft::ft_transfer_call(
+            account.clone(),
+            U128(token_amount),
+            None,
+            msg.clone(),
+            token_id,
+            1,
+            Gas(50_000_000_000_000),         // gas to attach, receiver's ft_on_transfer runs on it
+        );
+        let resolve = ext_self::resolve_withdraw_call(
+            WithdrawalPayout { tx_id, receiver: account, token, amount: U128(amount), msg: Some(msg) },
+            env::current_account_id(),
+            0,
+            Gas(10_000_000_000_000),         // gas to attach to the callback
+        );
+        (transfer, resolve)
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    // fallbacks
+
+    /// credit amount refunded by the receiver of ft_transfer_call, record withdrawal as failed if the call failed
+    ///
+    /// return true if receiver used the whole amount, an unreadable result counts as nothing used
+    #[private]
+    pub fn resolve_withdraw_call(
+        &mut self,
+        payout: WithdrawalPayout,
+    ) -> bool {
+        if env::promise_results_count() != 1 {
+            BridgeError::CallbackOnly.panic();
+        }
+        let WithdrawalPayout { tx_id, receiver, token, amount, msg } = payout;
+
+        let token_amount = self.to_token_amount(&token, amount.0);
+        let used = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => {
+                self.record_failed_withdrawal(tx_id, receiver, token, amount, msg);
+                return false;
+            }
+            // token contract reports amount kept by receiver
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
+                .map(|used| used.0.min(token_amount))
+                .unwrap_or(0),
+        };
+
+        // dust below one incognito unit stays in the vault and in its ledger
+        let unit = self.to_token_amount(&token, 1);
+        let unused = token_amount.saturating_sub(used);
+        let refund = unused / unit;
+        self.release_balance(&token, amount.0 - refund);
+        self.lock_balance(&token, unused % unit, unit);
+        if refund == 0 {
+            return true;
+        }
+        BridgeEvent::WithdrawRefundCredited {
+            tx_id: hex::encode(tx_id),
+            receiver: &receiver,
+            token: &token,
+            amount: U128(refund),
+        }.emit();
+        // rate limit is consumed again when the refund is claimed
+        self.release_rate_limit(&token, refund);
+        self.add_credit(&token, &receiver, refund);
+
+        false
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+    use crate::test_utils::{callback_results, json_result, new_vault};
+
+    fn used_result(used: u128) {
+        callback_results(vec![json_result(&U128(used))]);
+    }
+
+    fn payout() -> WithdrawalPayout {
+        WithdrawalPayout {
+            tx_id: [1u8; 32],
+            receiver: accounts(1),
+            token: accounts(3).to_string(),
+            amount: U128(10),
+            msg: Some("msg".to_string()),
+        }
+    }
+
+    fn setup_vault() -> Vault {
+        let mut vault = new_vault();
+        vault.token_decimals.insert(&accounts(3).to_string(), &12);
+        vault.lock_balance(&accounts(3).to_string(), 10_000, 1_000);
+        vault
+    }
+
+    #[test]
+    fn test_refund_credited() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        used_result(3_500);
+        assert!(!vault.resolve_withdraw_call(payout()));
+        // 6_500 refunded, 6 units credited, 500 dust stays locked
+        assert_eq!(vault.get_credit(token.clone(), accounts(1)).0, 6);
+        assert_eq!(vault.get_total_credit(token.clone()).0, 6);
+        assert_eq!(vault.get_locked_balance(token).0, 6_500);
+    }
+
+    #[test]
+    fn test_fully_used() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        used_result(10_000);
+        assert!(vault.resolve_withdraw_call(payout()));
+        assert_eq!(vault.get_credit(token.clone(), accounts(1)).0, 0);
+        assert_eq!(vault.get_locked_balance(token).0, 0);
+    }
+
+    #[test]
+    fn test_unreadable_result_refunded() {
+        let mut vault = setup_vault();
+        let token = accounts(3).to_string();
+        callback_results(vec![PromiseResult::Successful(b"not json".to_vec())]);
+        assert!(!vault.resolve_withdraw_call(payout()));
+        assert_eq!(vault.get_credit(token.clone(), accounts(1)).0, 10);
+        assert_eq!(vault.get_locked_balance(token).0, 10_000);
+    }
+
+    #[test]
+    fn test_failed_call_keeps_msg() {
+        let mut vault = setup_vault();
+        callback_results(vec![PromiseResult::Failed]);
+        assert!(!vault.resolve_withdraw_call(payout()));
+        assert_eq!(vault.get_failed_withdrawal([1u8; 32]).unwrap().msg, Some("msg".to_string()));
+    }
+}
@@ -0,0 +1,129 @@
+const nearAPI = require("near-api-js");
+const BN = require("bn.js");
+// compare gas burnt by withdraw (JSON proof) and withdraw_borsh (borsh proof)
+// usage: node benchmarkgas.js <burn tx id for withdraw> <burn tx id for withdraw_borsh>
+// each burn tx can be withdrawn once, so the two variants need two burn txs of similar proof size
+
+const { keyStores, KeyPair } = nearAPI;
+const keyStore = new keyStores.InMemoryKeyStore();
+const PRIVATE_KEY =
+    "4GrZBkRSEp8YT6ztXHUu9wzrDb3qrpFpyTzEsFR5yovjbGqt16aKQVR7WHoMUdBoNwe2NJRGZ22mt1o3j2wda1jk";
+const SENDER_ADDRESS = "cuongcute.testnet";
+const CONTRACT_ID = "496add2c24e17711d9512172901b5502df37e10493d247c371eb8dc3e4b173fc";
+const keyPair = KeyPair.fromString(PRIVATE_KEY);
+const { connect } = nearAPI;
+const axios = require('axios');
+
+class BorshInteractRequest {
+    constructor(fields) {
+        Object.assign(this, fields);
+    }
+}
+
+// field order must match BorshInteractRequest in bridge/src/borsh_request.rs
+const SCHEMA = new Map([[BorshInteractRequest, {
+    kind: 'struct',
+    fields: [
+        ['inst', ['u8']],
+        ['height', 'u128'],
+        ['inst_paths', [[32]]],
+        ['inst_path_is_lefts', ['u8']],
+        ['inst_root', [32]],
+        ['blk_data', [32]],
+        ['indexes', ['u8']],
+        ['signatures', [[64]]],
+        ['vs', ['u8']],
+    ],
+}]]);
+
+(async () => {
+    const [jsonTxId, borshTxId] = process.argv.slice(2);
+    await keyStore.setKey("testnet", SENDER_ADDRESS, keyPair);
+
+    const config = {
+        networkId: "testnet",
+        keyStore,
+        nodeUrl: "https://rpc.testnet.near.org",
+        walletUrl: "https://wallet.testnet.near.org",
+        helperUrl: "https://helper.testnet.near.org",
+        explorerUrl: "https://explorer.testnet.near.org",
+    };
+    const near = await connect(config);
+    const senderAccount = await near.account(SENDER_ADDRESS);
+
+    const jsonArgs = Buffer.from(JSON.stringify({ unshield_info: toJsonRequest(await getBurnProof(jsonTxId)) }));
+    const jsonOutcome = await senderAccount.functionCall({
+        contractId: CONTRACT_ID,
+        methodName: "withdraw",
+        args: jsonArgs,
+        gas: "300000000000000",
+    });
+
+    const borshArgs = nearAPI.utils.serialize.serialize(SCHEMA, toBorshRequest(await getBurnProof(borshTxId)));
+    const borshOutcome = await senderAccount.functionCall({
+        contractId: CONTRACT_ID,
+        methodName: "withdraw_borsh",
+        args: borshArgs,
+        gas: "300000000000000",
+    });
+
+    // first receipt runs the contract call, later ones are transfers and callbacks
+    console.table({
+        withdraw: { args_bytes: jsonArgs.length, gas_burnt: jsonOutcome.receipts_outcome[0].outcome.gas_burnt },
+        withdraw_borsh: { args_bytes: borshArgs.length, gas_burnt: borshOutcome.receipts_outcome[0].outcome.gas_burnt },
+    });
+})();
+
+async function getBurnProof(txId) {
+    const burnProof = await axios.post(
+        "http://127.0.0.1:9334",
+        {
+            "jsonrpc": "1.0",
+            "method": "getnearburnproof",
+            "params": [txId],
+            "id": 1
+        }
+    );
+    return burnProof.data.Result;
+}
+
+function toJsonRequest(res) {
+    return {
+        inst: res.Instruction,
+        height: BigInt('0x' + res.BeaconHeight).toString(),
+        inst_paths: res.BeaconInstPath.map(path => [...to32Bytes(path)]),
+        inst_path_is_lefts: res.BeaconInstPathIsLeft,
+        inst_root: [...to32Bytes(res.BeaconInstRoot)],
+        blk_data: [...to32Bytes(res.BeaconBlkData)],
+        indexes: res.BeaconSigIdxs,
+        signatures: res.BeaconSigs.map(signature => signature.slice(0, 128)),
+        vs: res.BeaconSigs.map(signature => Number(signature.slice(128))),
+    };
+}
+
+function toBorshRequest(res) {
+    const lefts = Buffer.alloc(Math.ceil(res.BeaconInstPathIsLeft.length / 8));
+    res.BeaconInstPathIsLeft.forEach((left, i) => {
+        if (left) {
+            lefts[i >> 3] |= 1 << (i % 8);
+        }
+    });
+    return new BorshInteractRequest({
+        inst: [...Buffer.from(res.Instruction, "hex")],
+        height: new BN(res.BeaconHeight, 16),
+        inst_paths: res.BeaconInstPath.map(to32Bytes),
+        inst_path_is_lefts: [...lefts],
+        inst_root: to32Bytes(res.BeaconInstRoot),
+        blk_data: to32Bytes(res.BeaconBlkData),
+        indexes: res.BeaconSigIdxs,
+        signatures: res.BeaconSigs.map(signature => Buffer.from(signature.slice(0, 128), "hex")),
+        vs: res.BeaconSigs.map(signature => Number(signature.slice(128))),
+    });
+}
+
+function to32Bytes(hexStr) {
+    const bytes = Buffer.from(hexStr, "hex");
+    const padded = Buffer.alloc(32);
+    bytes.copy(padded);
+    return padded;
+}
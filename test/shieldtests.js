@@ -73,7 +73,7 @@ const { connect } = nearAPI;
     //                 beacon3,
     //                 beacon4
     //             ],
-    //             height: 0,
+    //             height: "0",
     //         },
     //         gas: "300000000000000",
     //         amount: "0"
@@ -81,7 +81,7 @@ const { connect } = nearAPI;
     // );
 
     const beaconlist = await contract.get_beacons({
-        height: 0
+        height: "0"
     });
     console.log({beaconlist});
 
@@ -93,7 +93,8 @@ const { connect } = nearAPI;
                 incognito_address: incognitoAddress
             },
             gas: "300000000000000",
-            amount: "1000000000000000000000"
+            // receipt storage (about 0.003 NEAR) is paid from the deposit, the rest is shielded
+            amount: "10000000000000000000000"
         },
     );
 
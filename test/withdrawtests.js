@@ -63,7 +63,7 @@ const axios = require('axios');
         );
         let res = burnProof.data.Result;
         unshieldInfo.inst = res.Instruction;
-        unshieldInfo.height = Number('0x' + res.BeaconHeight);
+        unshieldInfo.height = BigInt('0x' + res.BeaconHeight).toString();
         let inst_paths = [];
         for (const inst_path of res.BeaconInstPath) {
             inst_paths.push(to32Bytes(inst_path));